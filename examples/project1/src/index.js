@@ -4,7 +4,7 @@ const webview = new Webview("./index.html");
 
 console.log(webview);
 
-webview.run(({event}) => {
+webview.run(({ event, width, height, paths, url }) => {
    switch (event) {
      case 'close':
        Deno.exit()
@@ -15,5 +15,14 @@ webview.run(({event}) => {
      case 'domContentLoaded':
        console.log("It works! domContentLoaded")
        break;
+     case 'resized':
+       console.log(`Window resized to ${width}x${height}`);
+       break;
+     case 'navigationFinished':
+       console.log(`Navigated to ${url}`);
+       break;
+     case 'fileDropped':
+       console.log("Files dropped", paths);
+       break;
      }
  }, 16);
\ No newline at end of file
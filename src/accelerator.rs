@@ -0,0 +1,175 @@
+use anyhow::bail;
+use std::str::FromStr;
+
+use crate::event::Modifiers;
+
+/// Key combination like `CmdOrCtrl+Shift+S`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accelerator {
+    pub modifiers: Modifiers,
+    /// X keysym name of the key, `s`, `F5`, `Return`...
+    pub key: String,
+}
+
+impl FromStr for Accelerator {
+    type Err = anyhow::Error;
+
+    fn from_str(accelerator: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = Modifiers::default();
+        let mut key = None;
+
+        for token in accelerator.split('+').map(str::trim) {
+            match token.to_lowercase().as_str() {
+                // there is no command key on linux
+                "cmdorctrl" | "commandorcontrol" | "ctrl" | "control" => modifiers.ctrl = true,
+                "cmd" | "command" | "super" | "meta" => modifiers.meta = true,
+                "alt" | "option" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                _ if key.is_none() => key = Some(key_name(token)?),
+                _ => bail!(
+                    "Invalid accelerator {}: only one key is allowed",
+                    accelerator
+                ),
+            }
+        }
+
+        match key {
+            Some(key) => Ok(Accelerator { modifiers, key }),
+            None => bail!("Invalid accelerator {}: missing key", accelerator),
+        }
+    }
+}
+
+impl Accelerator {
+    pub fn to_gtk(&self) -> (u32, gdk::ModifierType) {
+        let mut modifiers = gdk::ModifierType::empty();
+        if self.modifiers.shift {
+            modifiers |= gdk::ModifierType::SHIFT_MASK;
+        }
+        if self.modifiers.ctrl {
+            modifiers |= gdk::ModifierType::CONTROL_MASK;
+        }
+        if self.modifiers.alt {
+            modifiers |= gdk::ModifierType::MOD1_MASK;
+        }
+        if self.modifiers.meta {
+            modifiers |= gdk::ModifierType::SUPER_MASK;
+        }
+        (gdk::keyval_from_name(&self.key), modifiers)
+    }
+}
+
+fn key_name(token: &str) -> crate::Result<String> {
+    let lowercase = token.to_lowercase();
+    let mut chars = lowercase.chars();
+
+    let name = match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphanumeric() => c.to_string(),
+        (Some(c), None) => match c {
+            ',' => "comma",
+            '.' => "period",
+            '/' => "slash",
+            '\\' => "backslash",
+            ';' => "semicolon",
+            '\'' => "apostrophe",
+            '[' => "bracketleft",
+            ']' => "bracketright",
+            '-' => "minus",
+            '=' => "equal",
+            '`' => "grave",
+            _ => bail!("Unknown accelerator key {}", token),
+        }
+        .to_string(),
+        _ => match lowercase.as_str() {
+            "plus" => "plus",
+            "space" => "space",
+            "tab" => "Tab",
+            "enter" | "return" => "Return",
+            "esc" | "escape" => "Escape",
+            "backspace" => "BackSpace",
+            "delete" | "del" => "Delete",
+            "insert" => "Insert",
+            "home" => "Home",
+            "end" => "End",
+            "pageup" => "Page_Up",
+            "pagedown" => "Page_Down",
+            "up" => "Up",
+            "down" => "Down",
+            "left" => "Left",
+            "right" => "Right",
+            function
+                if function.starts_with('f')
+                    && matches!(function[1..].parse::<u8>(), Ok(1..=24)) =>
+            {
+                return Ok(function.to_uppercase());
+            }
+            _ => bail!("Unknown accelerator key {}", token),
+        }
+        .to_string(),
+    };
+
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(accelerator: &str) -> Accelerator {
+        accelerator.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_modifiers_and_key() {
+        let accelerator = parse("CmdOrCtrl+Shift+S");
+        assert_eq!(accelerator.key, "s");
+        assert_eq!(
+            accelerator.modifiers,
+            Modifiers {
+                shift: true,
+                ctrl: true,
+                alt: false,
+                meta: false,
+            }
+        );
+
+        let accelerator = parse("super + alt + F5");
+        assert_eq!(accelerator.key, "F5");
+        assert_eq!(
+            accelerator.modifiers,
+            Modifiers {
+                alt: true,
+                meta: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn maps_key_names_to_keysyms() {
+        assert_eq!(parse("Ctrl+,").key, "comma");
+        assert_eq!(parse("Ctrl+Plus").key, "plus");
+        assert_eq!(parse("Ctrl+Enter").key, "Return");
+        assert_eq!(parse("Esc").key, "Escape");
+        assert_eq!(parse("Shift+PageDown").key, "Page_Down");
+        assert_eq!(parse("Alt+1").key, "1");
+    }
+
+    #[test]
+    fn rejects_invalid_accelerators() {
+        for accelerator in &[
+            "",
+            "Ctrl+Shift",
+            "Ctrl+A+B",
+            "Ctrl+F25",
+            "Ctrl+Foo",
+            "Ctrl+é",
+        ] {
+            assert!(
+                accelerator.parse::<Accelerator>().is_err(),
+                "{} should be rejected",
+                accelerator
+            );
+        }
+    }
+}
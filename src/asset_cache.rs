@@ -0,0 +1,294 @@
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, Metadata},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use crate::helpers::to_hex;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// State of a file when it was last embedded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ManifestEntry {
+    /// modification time in nanoseconds since epoch
+    modified: u128,
+    size: u64,
+    hash: String,
+}
+
+impl ManifestEntry {
+    fn new(metadata: &Metadata, hash: String) -> Self {
+        Self {
+            modified: modified(metadata),
+            size: metadata.len(),
+            hash,
+        }
+    }
+}
+
+/// On-disk cache of compressed assets between builds, blobs are keyed by
+/// content hash so a file is only compressed again when its content changed.
+///
+/// Only what the last build used is kept, the rest is pruned on save.
+pub struct AssetCache {
+    dir: PathBuf,
+    /// absolute file path -> state at the previous build
+    previous: HashMap<String, ManifestEntry>,
+    /// absolute file path -> state at this build
+    manifest: Mutex<HashMap<String, ManifestEntry>>,
+    /// file names of the blobs read or written by this build
+    used: Mutex<HashSet<String>>,
+}
+
+impl AssetCache {
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let dir = fs::canonicalize(dir)?;
+
+        // a corrupted manifest only means we compress everything again
+        let previous = fs::read(dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|manifest| serde_json::from_slice(&manifest).ok())
+            .unwrap_or_default();
+
+        Ok(Self {
+            dir,
+            previous,
+            manifest: Mutex::new(HashMap::new()),
+            used: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Content hash recorded for this file, if it didn't change since.
+    pub fn cached_hash(&self, path: &Path, metadata: &Metadata) -> Option<String> {
+        let key = path.display().to_string();
+        let entry = self.previous.get(&key)?;
+        if entry.modified != 0
+            && entry.modified == modified(metadata)
+            && entry.size == metadata.len()
+        {
+            self.manifest.lock().unwrap().insert(key, entry.clone());
+            Some(entry.hash.clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn record(&self, path: &Path, metadata: &Metadata, hash: &str) {
+        self.manifest.lock().unwrap().insert(
+            path.display().to_string(),
+            ManifestEntry::new(metadata, hash.to_string()),
+        );
+    }
+
+    /// Stored bytes of a blob, `variant` identifies how it was compressed.
+    pub fn get(&self, hash: &str, variant: &str) -> Option<Vec<u8>> {
+        let bytes = fs::read(self.blob_path(hash, variant)).ok()?;
+        self.used.lock().unwrap().insert(blob_name(hash, variant));
+        Some(bytes)
+    }
+
+    pub fn insert(&self, hash: &str, variant: &str, bytes: &[u8]) -> io::Result<()> {
+        // write and rename so an interrupted build never leaves a truncated blob
+        let path = self.blob_path(hash, variant);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, path)?;
+        self.used.lock().unwrap().insert(blob_name(hash, variant));
+        Ok(())
+    }
+
+    /// Write the manifest of this build and delete the blobs it didn't use.
+    pub fn save(&self) -> io::Result<()> {
+        let manifest = self.manifest.lock().unwrap();
+        fs::write(
+            self.dir.join(MANIFEST_FILE),
+            serde_json::to_vec(&*manifest)?,
+        )?;
+
+        let used = self.used.lock().unwrap();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            // the directory may be shared, only touch our own files
+            if is_blob_name(&name) && !used.contains(&name) {
+                fs::remove_file(self.dir.join(&name))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn blob_path(&self, hash: &str, variant: &str) -> PathBuf {
+        self.dir.join(blob_name(hash, variant))
+    }
+}
+
+fn blob_name(hash: &str, variant: &str) -> String {
+    format!("{}.{}", hash, variant)
+}
+
+/// `<content hash>.<variant>`, `<content hash>.tmp` for a blob left half
+/// written by `insert`.
+fn is_blob_name(name: &str) -> bool {
+    let mut parts = name.splitn(2, '.');
+    let hash = parts.next().unwrap_or_default();
+    let variant = parts.next().unwrap_or_default();
+    hash.len() == 64
+        && hash.bytes().all(|byte| byte.is_ascii_hexdigit())
+        && !variant.is_empty()
+        && variant.bytes().all(|byte| byte.is_ascii_alphanumeric())
+}
+
+/// Hex encoded SHA-256 of the content.
+pub fn content_hash(bytes: &[u8]) -> String {
+    to_hex(digest(&SHA256, bytes).as_ref())
+}
+
+fn modified(metadata: &Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_nanos())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Cache directory next to an asset directory with a page.
+    fn fixture(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("wry-cache-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("app")).unwrap();
+        let page = dir.join("app/index.html");
+        fs::write(&page, "<h1>hello</h1>").unwrap();
+        (dir, page)
+    }
+
+    fn metadata(path: &Path) -> Metadata {
+        fs::metadata(path).unwrap()
+    }
+
+    #[test]
+    fn unchanged_files_hit() {
+        let (dir, page) = fixture("hit");
+        let cache = AssetCache::open(&dir.join("cache")).unwrap();
+        cache.record(&page, &metadata(&page), "abc");
+        cache.save().unwrap();
+
+        let cache = AssetCache::open(&dir.join("cache")).unwrap();
+        assert_eq!(
+            cache.cached_hash(&page, &metadata(&page)).as_deref(),
+            Some("abc")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn changed_files_miss() {
+        let (dir, page) = fixture("miss");
+        let cache = AssetCache::open(&dir.join("cache")).unwrap();
+        cache.record(&page, &metadata(&page), "abc");
+        cache.save().unwrap();
+
+        // same size, later modification
+        std::thread::sleep(Duration::from_millis(20));
+        fs::write(&page, "<h1>HELLO</h1>").unwrap();
+        let cache = AssetCache::open(&dir.join("cache")).unwrap();
+        assert_eq!(cache.cached_hash(&page, &metadata(&page)), None);
+
+        // another size
+        fs::write(&page, "<h1>hello world</h1>").unwrap();
+        assert_eq!(cache.cached_hash(&page, &metadata(&page)), None);
+
+        let other = dir.join("app/other.html");
+        fs::write(&other, "<h1>other</h1>").unwrap();
+        assert_eq!(cache.cached_hash(&other, &metadata(&other)), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn corrupted_manifests_are_ignored() {
+        let (dir, page) = fixture("corrupted");
+        let cache = AssetCache::open(&dir.join("cache")).unwrap();
+        cache.record(&page, &metadata(&page), "abc");
+        cache.save().unwrap();
+        fs::write(dir.join("cache").join(MANIFEST_FILE), "{\"truncated").unwrap();
+
+        let cache = AssetCache::open(&dir.join("cache")).unwrap();
+        assert_eq!(cache.cached_hash(&page, &metadata(&page)), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn blobs_round_trip() {
+        let (dir, _) = fixture("blobs");
+        let cache = AssetCache::open(&dir.join("cache")).unwrap();
+        let hash = content_hash(b"hello");
+        assert_eq!(cache.get(&hash, "zstd19"), None);
+        cache.insert(&hash, "zstd19", b"compressed").unwrap();
+        assert_eq!(cache.get(&hash, "zstd19"), Some(b"compressed".to_vec()));
+        assert_eq!(cache.get(&hash, "zstd3"), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_only_what_the_last_build_used() {
+        let (dir, page) = fixture("prune");
+        let removed = dir.join("app/removed.html");
+        fs::write(&removed, "<h1>gone</h1>").unwrap();
+        let cache_dir = dir.join("cache");
+        let (kept, stale) = (content_hash(b"kept"), content_hash(b"stale"));
+
+        let cache = AssetCache::open(&cache_dir).unwrap();
+        cache.record(&page, &metadata(&page), &kept);
+        cache.record(&removed, &metadata(&removed), &stale);
+        cache.insert(&kept, "zstd19", b"kept").unwrap();
+        cache.insert(&stale, "zstd19", b"stale").unwrap();
+        cache.save().unwrap();
+        fs::write(cache_dir.join(format!("{}.tmp", stale)), "partial").unwrap();
+        fs::write(cache_dir.join("notes.txt"), "not ours").unwrap();
+
+        // the next build only sees the page
+        let page_metadata = metadata(&page);
+        let removed_metadata = metadata(&removed);
+        let cache = AssetCache::open(&cache_dir).unwrap();
+        assert_eq!(cache.cached_hash(&page, &page_metadata), Some(kept.clone()));
+        assert!(cache.get(&kept, "zstd19").is_some());
+        cache.save().unwrap();
+
+        assert!(cache_dir.join(format!("{}.zstd19", kept)).exists());
+        assert!(!cache_dir.join(format!("{}.zstd19", stale)).exists());
+        assert!(!cache_dir.join(format!("{}.tmp", stale)).exists());
+        assert!(cache_dir.join("notes.txt").exists());
+
+        let cache = AssetCache::open(&cache_dir).unwrap();
+        assert_eq!(cache.cached_hash(&page, &page_metadata), Some(kept));
+        assert_eq!(cache.cached_hash(&removed, &removed_metadata), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recognizes_blob_names() {
+        let hash = content_hash(b"hello");
+        assert!(is_blob_name(&format!("{}.zstd19", hash)));
+        assert!(is_blob_name(&format!("{}.tmp", hash)));
+        assert!(!is_blob_name(&format!("{}.zstd19.bak", hash)));
+        assert!(!is_blob_name(MANIFEST_FILE));
+        assert!(!is_blob_name(&hash));
+        assert!(!is_blob_name(&format!("{}.", hash)));
+        assert!(!is_blob_name("abc.zstd19"));
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+use crate::protocol::mime_type;
+
+/// Files of an app, served to the webview and readable by the backend.
+///
+/// Keys are paths relative to the asset root, with `/` separators.
+pub trait Assets {
+    /// Get the content of the asset.
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Get the MIME type of the asset.
+    fn mime_type(&self, key: &str) -> Option<String>;
+
+    /// Get the uncompressed size of the asset.
+    fn size(&self, key: &str) -> Option<u64>;
+
+    /// List the keys of all assets.
+    fn keys(&self) -> Vec<String>;
+
+    /// Open the asset for streaming, without loading it whole in memory when
+    /// the storage allows it.
+    fn open(&self, key: &str) -> Option<Box<dyn Read + Send>> {
+        Some(Box::new(Cursor::new(self.get(key)?)))
+    }
+
+    /// Read a range of the asset, seeking to its start when the storage
+    /// allows it.
+    fn read_range(&self, key: &str, range: Range<u64>) -> Option<Box<dyn Read + Send>> {
+        let mut reader = self.open(key)?;
+        std::io::copy(&mut (&mut reader).take(range.start), &mut std::io::sink()).ok()?;
+        Some(Box::new(reader.take(range.end.saturating_sub(range.start))))
+    }
+
+    /// Directory backing the assets, modules are loaded from it directly.
+    fn root(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Whether assets can change while the app is running.
+    fn is_mutable(&self) -> bool {
+        false
+    }
+}
+
+/// Assets read from a directory, used by `wry run`.
+pub struct DirAssets {
+    root: PathBuf,
+}
+
+impl DirAssets {
+    pub fn new(root: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            root: std::fs::canonicalize(root)?,
+        })
+    }
+
+    fn find_file(&self, key: &str) -> Option<PathBuf> {
+        // canonicalize resolve symlinks, make sure we are still inside our root
+        let file = std::fs::canonicalize(self.root.join(key.trim_start_matches('/'))).ok()?;
+        if !file.starts_with(&self.root) || !file.is_file() {
+            return None;
+        }
+        Some(file)
+    }
+}
+
+impl Assets for DirAssets {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.find_file(key)?).ok()
+    }
+
+    fn mime_type(&self, key: &str) -> Option<String> {
+        self.find_file(key)?;
+        Some(mime_type(key).to_string())
+    }
+
+    fn size(&self, key: &str) -> Option<u64> {
+        Some(std::fs::metadata(self.find_file(key)?).ok()?.len())
+    }
+
+    fn keys(&self) -> Vec<String> {
+        WalkDir::new(&self.root)
+            .follow_links(true)
+            .into_iter()
+            // only list what `get` serves, links leading out of the root aren't
+            .filter_entry(|entry| {
+                !entry.path_is_symlink()
+                    || std::fs::canonicalize(entry.path())
+                        .map_or(false, |target| target.starts_with(&self.root))
+            })
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| {
+                let key = entry.path().strip_prefix(&self.root).ok()?;
+                Some(key.to_string_lossy().replace('\\', "/"))
+            })
+            .collect()
+    }
+
+    fn open(&self, key: &str) -> Option<Box<dyn Read + Send>> {
+        Some(Box::new(File::open(self.find_file(key)?).ok()?))
+    }
+
+    fn read_range(&self, key: &str, range: Range<u64>) -> Option<Box<dyn Read + Send>> {
+        let mut file = File::open(self.find_file(key)?).ok()?;
+        file.seek(SeekFrom::Start(range.start)).ok()?;
+        Some(Box::new(file.take(range.end.saturating_sub(range.start))))
+    }
+
+    fn root(&self) -> Option<&Path> {
+        Some(&self.root)
+    }
+
+    fn is_mutable(&self) -> bool {
+        true
+    }
+}
+
+/// Assets read from a zip archive.
+pub struct ZipAssets {
+    archive: Mutex<ZipArchive<File>>,
+}
+
+impl ZipAssets {
+    pub fn open(path: &Path) -> crate::Result<Self> {
+        let archive = ZipArchive::new(File::open(path)?)?;
+        Ok(Self {
+            archive: Mutex::new(archive),
+        })
+    }
+}
+
+impl Assets for ZipAssets {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut archive = self.archive.lock().unwrap();
+        let mut file = archive.by_name(key.trim_start_matches('/')).ok()?;
+        if file.is_dir() {
+            return None;
+        }
+        let mut bytes = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    fn mime_type(&self, key: &str) -> Option<String> {
+        self.size(key)?;
+        Some(mime_type(key).to_string())
+    }
+
+    fn size(&self, key: &str) -> Option<u64> {
+        let mut archive = self.archive.lock().unwrap();
+        let file = archive.by_name(key.trim_start_matches('/')).ok()?;
+        if file.is_dir() {
+            None
+        } else {
+            Some(file.size())
+        }
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let archive = self.archive.lock().unwrap();
+        archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Assets of `upper` shadow the ones of `lower`, used to ship patches or
+/// themes in a directory next to a compiled binary.
+///
+/// Only frontend assets can be replaced, the backend modules are always
+/// loaded from `lower`.
+pub struct OverlayAssets {
+    upper: Box<dyn Assets>,
+    lower: Box<dyn Assets>,
+    protected: Vec<String>,
+}
+
+impl OverlayAssets {
+    pub fn new(upper: Box<dyn Assets>, lower: Box<dyn Assets>) -> Self {
+        Self {
+            upper,
+            lower,
+            protected: Vec::new(),
+        }
+    }
+
+    /// Always read the key from `lower`, for the backend entry point.
+    pub fn with_protected(mut self, key: &str) -> Self {
+        self.protected.push(key.into());
+        self
+    }
+
+    fn layer(&self, key: &str) -> &dyn Assets {
+        let key = key.trim_start_matches('/');
+        if !self
+            .protected
+            .iter()
+            .any(|protected| protected.trim_start_matches('/') == key)
+            && self.upper.size(key).is_some()
+        {
+            self.upper.as_ref()
+        } else {
+            self.lower.as_ref()
+        }
+    }
+}
+
+impl Assets for OverlayAssets {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.layer(key).get(key)
+    }
+
+    fn mime_type(&self, key: &str) -> Option<String> {
+        self.layer(key).mime_type(key)
+    }
+
+    fn size(&self, key: &str) -> Option<u64> {
+        self.layer(key).size(key)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let mut keys = self.upper.keys();
+        keys.extend(self.lower.keys());
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    fn open(&self, key: &str) -> Option<Box<dyn Read + Send>> {
+        self.layer(key).open(key)
+    }
+
+    fn read_range(&self, key: &str, range: Range<u64>) -> Option<Box<dyn Read + Send>> {
+        self.layer(key).read_range(key, range)
+    }
+
+    fn root(&self) -> Option<&Path> {
+        self.lower.root()
+    }
+
+    fn is_mutable(&self) -> bool {
+        self.upper.is_mutable() || self.lower.is_mutable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// App directory `app` next to a `secret.txt` it must not serve.
+    fn fixture(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wry-assets-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("app/css")).unwrap();
+        std::fs::write(dir.join("app/index.html"), "index").unwrap();
+        std::fs::write(dir.join("app/css/app.css"), "css").unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        dir
+    }
+
+    #[test]
+    fn dir_assets_reads_inside_the_root() {
+        let dir = fixture("inside");
+        let assets = DirAssets::new(&dir.join("app")).unwrap();
+        assert_eq!(assets.get("index.html"), Some(b"index".to_vec()));
+        assert_eq!(assets.get("/css/app.css"), Some(b"css".to_vec()));
+        assert_eq!(assets.size("css/app.css"), Some(3));
+        assert_eq!(assets.get("css"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn read(reader: Option<Box<dyn Read + Send>>) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        reader?.read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    #[test]
+    fn dir_assets_reads_ranges() {
+        let dir = fixture("range");
+        let assets = DirAssets::new(&dir.join("app")).unwrap();
+        assert_eq!(
+            read(assets.read_range("index.html", 1..4)),
+            Some(b"nde".to_vec())
+        );
+        assert_eq!(
+            read(assets.read_range("index.html", 3..5)),
+            Some(b"ex".to_vec())
+        );
+        assert_eq!(read(assets.read_range("../secret.txt", 0..6)), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn default_ranges_skip_the_start() {
+        struct Memory;
+        impl Assets for Memory {
+            fn get(&self, _key: &str) -> Option<Vec<u8>> {
+                Some(b"0123456789".to_vec())
+            }
+            fn mime_type(&self, _key: &str) -> Option<String> {
+                None
+            }
+            fn size(&self, _key: &str) -> Option<u64> {
+                Some(10)
+            }
+            fn keys(&self) -> Vec<String> {
+                Vec::new()
+            }
+        }
+        assert_eq!(read(Memory.read_range("a", 2..5)), Some(b"234".to_vec()));
+        assert_eq!(read(Memory.read_range("a", 8..20)), Some(b"89".to_vec()));
+    }
+
+    #[test]
+    fn dir_assets_rejects_paths_escaping_the_root() {
+        let dir = fixture("escape");
+        let assets = DirAssets::new(&dir.join("app")).unwrap();
+        assert_eq!(assets.get("../secret.txt"), None);
+        assert_eq!(assets.get("css/../../secret.txt"), None);
+        assert_eq!(assets.size("../secret.txt"), None);
+        assert!(assets.open("../secret.txt").is_none());
+        let absolute = dir.join("secret.txt");
+        assert_eq!(assets.get(&absolute.to_string_lossy()), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dir_assets_rejects_symlinks_escaping_the_root() {
+        let dir = fixture("symlink");
+        std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("app/link.txt")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("app/parent")).unwrap();
+        let assets = DirAssets::new(&dir.join("app")).unwrap();
+        assert_eq!(assets.get("link.txt"), None);
+        assert_eq!(assets.get("parent/secret.txt"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dir_assets_lists_what_it_serves() {
+        let dir = fixture("keys");
+        std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("app/link.txt")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("app/parent")).unwrap();
+        // links staying inside the root are served
+        std::os::unix::fs::symlink(dir.join("app/css"), dir.join("app/styles")).unwrap();
+        let assets = DirAssets::new(&dir.join("app")).unwrap();
+        let mut keys = assets.keys();
+        keys.sort();
+        assert_eq!(keys, ["css/app.css", "index.html", "styles/app.css"]);
+        assert!(keys.iter().all(|key| assets.get(key).is_some()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Archive with a page, a stylesheet in a directory and the directory
+    /// entry itself.
+    fn zip_fixture(name: &str) -> (PathBuf, ZipAssets) {
+        use std::io::Write;
+        use zip::{write::FileOptions, ZipWriter};
+
+        let dir = fixture(name);
+        let path = dir.join("app.zip");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file("index.html", FileOptions::default())
+            .unwrap();
+        zip.write_all(b"<h1>zip</h1>").unwrap();
+        zip.add_directory("css/", FileOptions::default()).unwrap();
+        zip.start_file("css/app.css", FileOptions::default())
+            .unwrap();
+        zip.write_all(b"body {}").unwrap();
+        zip.finish().unwrap();
+        let assets = ZipAssets::open(&path).unwrap();
+        (dir, assets)
+    }
+
+    #[test]
+    fn zip_assets_reads_files() {
+        let (dir, assets) = zip_fixture("zip");
+        assert_eq!(assets.get("index.html"), Some(b"<h1>zip</h1>".to_vec()));
+        assert_eq!(assets.get("/css/app.css"), Some(b"body {}".to_vec()));
+        assert_eq!(assets.size("css/app.css"), Some(7));
+        assert_eq!(assets.mime_type("css/app.css").as_deref(), Some("text/css"));
+        assert_eq!(
+            read(assets.open("index.html")),
+            Some(b"<h1>zip</h1>".to_vec())
+        );
+        assert_eq!(
+            read(assets.read_range("css/app.css", 5..7)),
+            Some(b"{}".to_vec())
+        );
+        assert_eq!(assets.get("missing.html"), None);
+        assert_eq!(assets.mime_type("missing.html"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn zip_assets_skips_directories() {
+        let (dir, assets) = zip_fixture("zip-dirs");
+        assert_eq!(assets.get("css/"), None);
+        assert_eq!(assets.size("css/"), None);
+        let mut keys = assets.keys();
+        keys.sort();
+        assert_eq!(keys, ["css/app.css", "index.html"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn zip_assets_refuses_other_files() {
+        let dir = fixture("zip-invalid");
+        assert!(ZipAssets::open(&dir.join("secret.txt")).is_err());
+        assert!(ZipAssets::open(&dir.join("missing.zip")).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// `app` as the lower layer and a `patches` directory replacing its page
+    /// and adding a theme.
+    fn overlay_fixture(name: &str) -> (PathBuf, OverlayAssets) {
+        let dir = fixture(name);
+        std::fs::create_dir_all(dir.join("patches")).unwrap();
+        std::fs::write(dir.join("patches/index.html"), "patched").unwrap();
+        std::fs::write(dir.join("patches/theme.css"), "theme").unwrap();
+        std::fs::write(dir.join("app/index.js"), "backend").unwrap();
+        std::fs::write(dir.join("patches/index.js"), "replaced").unwrap();
+        let assets = OverlayAssets::new(
+            Box::new(DirAssets::new(&dir.join("patches")).unwrap()),
+            Box::new(DirAssets::new(&dir.join("app")).unwrap()),
+        );
+        (dir, assets)
+    }
+
+    #[test]
+    fn overlay_assets_shadows_the_lower_layer() {
+        let (dir, assets) = overlay_fixture("overlay");
+        assert_eq!(assets.get("index.html"), Some(b"patched".to_vec()));
+        assert_eq!(assets.size("index.html"), Some(7));
+        assert_eq!(read(assets.open("index.html")), Some(b"patched".to_vec()));
+        assert_eq!(
+            read(assets.read_range("index.html", 0..5)),
+            Some(b"patch".to_vec())
+        );
+        assert_eq!(assets.get("theme.css"), Some(b"theme".to_vec()));
+        // missing from the overlay
+        assert_eq!(assets.get("css/app.css"), Some(b"css".to_vec()));
+        assert_eq!(assets.get("missing.html"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn overlay_assets_lists_both_layers_once() {
+        let (dir, assets) = overlay_fixture("overlay-keys");
+        assert_eq!(
+            assets.keys(),
+            ["css/app.css", "index.html", "index.js", "theme.css"]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn overlay_assets_never_replaces_protected_keys() {
+        let (dir, assets) = overlay_fixture("overlay-protected");
+        let assets = assets.with_protected("index.js");
+        assert_eq!(assets.get("index.js"), Some(b"backend".to_vec()));
+        assert_eq!(assets.get("/index.js"), Some(b"backend".to_vec()));
+        assert_eq!(assets.size("index.js"), Some(7));
+        assert_eq!(read(assets.open("index.js")), Some(b"backend".to_vec()));
+        // other keys are still replaced
+        assert_eq!(assets.get("index.html"), Some(b"patched".to_vec()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn overlay_assets_loads_modules_from_the_lower_layer() {
+        let (dir, assets) = overlay_fixture("overlay-root");
+        let app = std::fs::canonicalize(dir.join("app")).unwrap();
+        assert_eq!(assets.root(), Some(app.as_path()));
+        assert!(assets.is_mutable());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
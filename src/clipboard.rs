@@ -0,0 +1,132 @@
+#[cfg(target_os = "linux")]
+use gtk::{Clipboard, TargetEntry, TargetFlags};
+
+// targets of `write_html`, matched by the info passed to gtk
+#[cfg(target_os = "linux")]
+const HTML_INFO: u32 = 0;
+#[cfg(target_os = "linux")]
+const TEXT_INFO: u32 = 1;
+
+// the clipboard may be used before any webview initialized gtk
+#[cfg(target_os = "linux")]
+fn clipboard() -> crate::Result<Clipboard> {
+    gtk::init()?;
+    Ok(Clipboard::get(&gdk::SELECTION_CLIPBOARD))
+}
+
+#[cfg(target_os = "linux")]
+pub fn read_text() -> crate::Result<Option<String>> {
+    Ok(clipboard()?.wait_for_text().map(|text| text.to_string()))
+}
+
+#[cfg(target_os = "linux")]
+pub fn write_text(text: &str) -> crate::Result<()> {
+    let clipboard = clipboard()?;
+    clipboard.set_text(text);
+    // hand the content to the clipboard manager, it outlives the app
+    clipboard.store();
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn read_html() -> crate::Result<Option<String>> {
+    let data = match clipboard()?.wait_for_contents(&gdk::Atom::intern("text/html")) {
+        Some(selection) => selection.get_data(),
+        None => return Ok(None),
+    };
+
+    // firefox and chromium copy utf-16 with a byte order mark
+    let html = match data.as_slice() {
+        [0xff, 0xfe, rest @ ..] => String::from_utf16_lossy(
+            &rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        _ => String::from_utf8_lossy(&data).into_owned(),
+    };
+    Ok(Some(html.trim_end_matches('\0').to_string()))
+}
+
+/// Copy html, apps pasting plain text get `text`.
+#[cfg(target_os = "linux")]
+pub fn write_html(html: &str, text: &str) -> crate::Result<()> {
+    let targets = [
+        TargetEntry::new("text/html", TargetFlags::empty(), HTML_INFO),
+        TargetEntry::new("UTF8_STRING", TargetFlags::empty(), TEXT_INFO),
+        TargetEntry::new("text/plain;charset=utf-8", TargetFlags::empty(), TEXT_INFO),
+        TargetEntry::new("text/plain", TargetFlags::empty(), TEXT_INFO),
+    ];
+    let html = html.to_string();
+    let text = text.to_string();
+
+    let clipboard = clipboard()?;
+    let success = clipboard.set_with_data(&targets, move |_clipboard, selection, info| {
+        if info == HTML_INFO {
+            selection.set(&selection.get_target(), 8, html.as_bytes());
+        } else {
+            selection.set_text(&text);
+        }
+    });
+    if !success {
+        return Err(anyhow::anyhow!("Unable to write to the clipboard"));
+    }
+    clipboard.store();
+    Ok(())
+}
+
+/// PNG encoded image of the clipboard.
+#[cfg(target_os = "linux")]
+pub fn read_image() -> crate::Result<Option<Vec<u8>>> {
+    match clipboard()?.wait_for_image() {
+        Some(pixbuf) => Ok(Some(pixbuf.save_to_bufferv("png", &[])?)),
+        None => Ok(None),
+    }
+}
+
+/// Copy an image, in any format gdk-pixbuf can read.
+#[cfg(target_os = "linux")]
+pub fn write_image(bytes: &[u8]) -> crate::Result<()> {
+    let pixbuf = crate::helpers::pixbuf_from_bytes(bytes)?;
+    let clipboard = clipboard()?;
+    clipboard.set_image(&pixbuf);
+    clipboard.store();
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unsupported<T>() -> crate::Result<T> {
+    Err(anyhow::anyhow!(
+        "Clipboard is not supported on this platform yet"
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_text() -> crate::Result<Option<String>> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn write_text(_text: &str) -> crate::Result<()> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_html() -> crate::Result<Option<String>> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn write_html(_html: &str, _text: &str) -> crate::Result<()> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_image() -> crate::Result<Option<Vec<u8>>> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn write_image(_bytes: &[u8]) -> crate::Result<()> {
+    unsupported()
+}
@@ -0,0 +1,385 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+use wry::webview::WebView;
+
+#[cfg(target_os = "linux")]
+use crate::event::Event;
+#[cfg(target_os = "linux")]
+use glib::translate::{from_glib_none, mut_override, ToGlibPtr};
+#[cfg(target_os = "linux")]
+use gtk::prelude::*;
+#[cfg(target_os = "linux")]
+use gtk::{
+    ButtonsType, DialogFlags, FileChooserAction, FileChooserDialog, MessageDialog, MessageType,
+    ResponseType,
+};
+#[cfg(target_os = "linux")]
+use serde_json::{json, Value};
+#[cfg(target_os = "linux")]
+use std::cell::Cell;
+#[cfg(target_os = "linux")]
+use webkit2gtk::{ScriptDialog, ScriptDialogType, WebViewExt};
+
+#[cfg(target_os = "linux")]
+thread_local! {
+  static NEXT_REQUEST_ID: Cell<u64> = Cell::new(0);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileDialogKind {
+    Open,
+    Save,
+    PickFolder,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileFilter {
+    pub name: String,
+    /// without the leading dot
+    pub extensions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDialogOptions {
+    #[serde(default)]
+    pub title: Option<String>,
+    /// folder to start in, or file selected by default
+    #[serde(default)]
+    pub default_path: Option<PathBuf>,
+    #[serde(default)]
+    pub filters: Vec<FileFilter>,
+    /// allow selecting several files, only used to open files
+    #[serde(default)]
+    pub multiple: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageLevel {
+    Info,
+    Warning,
+    Error,
+    Question,
+}
+
+impl Default for MessageLevel {
+    fn default() -> Self {
+        MessageLevel::Info
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageOptions {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub level: MessageLevel,
+    /// button labels with `_` before the mnemonic, the last one is the
+    /// default, `_OK` when empty
+    #[serde(default)]
+    pub buttons: Vec<String>,
+    /// show a text entry prefilled with this value
+    #[serde(default)]
+    pub input: Option<String>,
+}
+
+/// Dialogs answer asynchronously, their result is pushed as a
+/// `dialogResponse` event carrying this id.
+#[cfg(target_os = "linux")]
+fn next_request_id() -> u64 {
+    NEXT_REQUEST_ID.with(|cell| {
+        let request_id = cell.get();
+        cell.set(request_id + 1);
+        request_id
+    })
+}
+
+/// Show a file dialog modal to the webview window.
+///
+/// The result is a path, a list of paths with `multiple`, or `null` when
+/// the user cancelled.
+#[cfg(target_os = "linux")]
+pub fn file_dialog(
+    id: u64,
+    webview: &WebView,
+    kind: FileDialogKind,
+    options: FileDialogOptions,
+) -> crate::Result<u64> {
+    let request_id = next_request_id();
+
+    let (action, accept) = match kind {
+        FileDialogKind::Open => (FileChooserAction::Open, "_Open"),
+        FileDialogKind::Save => (FileChooserAction::Save, "_Save"),
+        FileDialogKind::PickFolder => (FileChooserAction::SelectFolder, "_Select"),
+    };
+    let dialog = FileChooserDialog::with_buttons(
+        options.title.as_deref(),
+        Some(webview.window()),
+        action,
+        &[
+            ("_Cancel", ResponseType::Cancel),
+            (accept, ResponseType::Accept),
+        ],
+    );
+    dialog.set_modal(true);
+    dialog.set_do_overwrite_confirmation(true);
+
+    let multiple = options.multiple && kind == FileDialogKind::Open;
+    dialog.set_select_multiple(multiple);
+
+    for filter in &options.filters {
+        let file_filter = gtk::FileFilter::new();
+        file_filter.set_name(Some(&filter.name));
+        for extension in &filter.extensions {
+            file_filter.add_pattern(&format!("*.{}", extension.trim_start_matches('.')));
+        }
+        dialog.add_filter(&file_filter);
+    }
+
+    if let Some(path) = &options.default_path {
+        if path.is_dir() {
+            dialog.set_current_folder(path);
+        } else if kind == FileDialogKind::Save {
+            // the file may not exist yet, gtk only selects existing files
+            if let Some(folder) = path.parent() {
+                dialog.set_current_folder(folder);
+            }
+            if let Some(name) = path.file_name() {
+                dialog.set_current_name(name);
+            }
+        } else {
+            dialog.set_filename(path);
+        }
+    }
+
+    dialog.connect_response(move |dialog, response| {
+        let result = if response == ResponseType::Accept {
+            let paths = dialog.get_filenames();
+            if multiple {
+                json!(paths)
+            } else {
+                json!(paths.into_iter().next())
+            }
+        } else {
+            Value::Null
+        };
+        unsafe { dialog.destroy() };
+        crate::push_event(id, Event::DialogResponse { request_id, result });
+    });
+    dialog.show_all();
+
+    Ok(request_id)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn file_dialog(
+    _id: u64,
+    _webview: &WebView,
+    _kind: FileDialogKind,
+    _options: FileDialogOptions,
+) -> crate::Result<u64> {
+    Err(anyhow::anyhow!(
+        "File dialogs are not supported on this platform yet"
+    ))
+}
+
+/// Show a message box modal to the webview window.
+///
+/// The result is `{ button, text }`, `button` being the index of the clicked
+/// button or `null` when the dialog was closed, `text` the entry content
+/// when `input` was given.
+#[cfg(target_os = "linux")]
+pub fn message_dialog(id: u64, webview: &WebView, options: MessageOptions) -> crate::Result<u64> {
+    let request_id = next_request_id();
+
+    let (dialog, entry) =
+        build_message_dialog(Some(webview.window().upcast_ref::<gtk::Window>()), &options);
+    dialog.connect_response(move |dialog, response| {
+        let result = json!({
+            "button": clicked_button(response),
+            "text": entry.as_ref().map(|entry| entry.get_text().to_string()),
+        });
+        unsafe { dialog.destroy() };
+        crate::push_event(id, Event::DialogResponse { request_id, result });
+    });
+    dialog.show_all();
+
+    Ok(request_id)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn message_dialog(
+    _id: u64,
+    _webview: &WebView,
+    _options: MessageOptions,
+) -> crate::Result<u64> {
+    Err(anyhow::anyhow!(
+        "Message dialogs are not supported on this platform yet"
+    ))
+}
+
+/// Answer `alert`, `confirm` and `prompt` of the page with our own dialogs.
+///
+/// The page is blocked until they return, so they run modal right away.
+#[cfg(target_os = "linux")]
+pub fn connect_script_dialogs(view: &webkit2gtk::WebView) {
+    view.connect_script_dialog(|view, script_dialog| {
+        let message = script_dialog.get_message().to_string();
+        let dialog_type = script_dialog.get_dialog_type();
+
+        let options = match dialog_type {
+            ScriptDialogType::Alert => MessageOptions {
+                message,
+                ..Default::default()
+            },
+            ScriptDialogType::Confirm => MessageOptions {
+                message,
+                level: MessageLevel::Question,
+                buttons: vec!["_Cancel".into(), "_OK".into()],
+                ..Default::default()
+            },
+            ScriptDialogType::Prompt => MessageOptions {
+                message,
+                level: MessageLevel::Question,
+                buttons: vec!["_Cancel".into(), "_OK".into()],
+                input: Some(script_dialog.prompt_get_default_text().to_string()),
+                ..Default::default()
+            },
+            ScriptDialogType::BeforeUnloadConfirm => MessageOptions {
+                message: "Leave this page? Changes you made may not be saved.".into(),
+                level: MessageLevel::Warning,
+                buttons: vec!["_Stay".into(), "_Leave".into()],
+                ..Default::default()
+            },
+            // let webkit handle what we don't know about
+            _ => return false,
+        };
+
+        let window = view
+            .get_toplevel()
+            .and_then(|window| window.downcast::<gtk::Window>().ok());
+        let (dialog, entry) = build_message_dialog(window.as_ref(), &options);
+        // answered from the response handler so the loop keeps polling,
+        // the page stays blocked until the script dialog is closed
+        let pending = PendingScriptDialog::new(script_dialog);
+        dialog.connect_response(move |dialog, response| {
+            let confirmed = clicked_button(response) == Some(1);
+            let script_dialog = pending.script_dialog();
+            match (dialog_type, &entry) {
+                (ScriptDialogType::Prompt, Some(entry)) if confirmed => {
+                    script_dialog.prompt_set_text(&entry.get_text())
+                }
+                (ScriptDialogType::Confirm, _) | (ScriptDialogType::BeforeUnloadConfirm, _) => {
+                    script_dialog.confirm_set_confirmed(confirmed)
+                }
+                // a cancelled prompt returns null
+                _ => {}
+            }
+            pending.close();
+            unsafe { dialog.destroy() };
+        });
+        dialog.show_all();
+        true
+    });
+}
+
+/// A script dialog kept alive until our dialog answers it, the wrapper's
+/// copy doesn't take a reference.
+#[cfg(target_os = "linux")]
+struct PendingScriptDialog {
+    dialog: *mut webkit2gtk_sys::WebKitScriptDialog,
+    closed: Cell<bool>,
+}
+
+#[cfg(target_os = "linux")]
+impl PendingScriptDialog {
+    fn new(dialog: &ScriptDialog) -> Self {
+        let dialog: *const webkit2gtk_sys::WebKitScriptDialog = dialog.to_glib_none().0;
+        Self {
+            dialog: unsafe { webkit2gtk_sys::webkit_script_dialog_ref(mut_override(dialog)) },
+            closed: Cell::new(false),
+        }
+    }
+
+    fn script_dialog(&self) -> ScriptDialog {
+        unsafe { from_glib_none(self.dialog) }
+    }
+
+    /// hands the result back to the page
+    fn close(&self) {
+        if !self.closed.replace(true) {
+            unsafe { webkit2gtk_sys::webkit_script_dialog_close(self.dialog) };
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for PendingScriptDialog {
+    fn drop(&mut self) {
+        // our dialog went away without an answer, don't leave the page hanging
+        self.close();
+        unsafe { webkit2gtk_sys::webkit_script_dialog_unref(self.dialog) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn build_message_dialog(
+    parent: Option<&gtk::Window>,
+    options: &MessageOptions,
+) -> (MessageDialog, Option<gtk::Entry>) {
+    let message_type = match options.level {
+        MessageLevel::Info => MessageType::Info,
+        MessageLevel::Warning => MessageType::Warning,
+        MessageLevel::Error => MessageType::Error,
+        MessageLevel::Question => MessageType::Question,
+    };
+    let dialog = MessageDialog::new(
+        parent,
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        message_type,
+        ButtonsType::None,
+        &options.message,
+    );
+    if let Some(title) = &options.title {
+        dialog.set_title(title);
+    }
+
+    let buttons = if options.buttons.is_empty() {
+        vec!["_OK".to_string()]
+    } else {
+        options.buttons.clone()
+    };
+    for (index, label) in buttons.iter().enumerate() {
+        dialog.add_button(label, ResponseType::Other(index as u16));
+    }
+    dialog.set_default_response(ResponseType::Other(buttons.len() as u16 - 1));
+
+    let entry = options.input.as_ref().map(|text| {
+        let entry = gtk::Entry::new();
+        entry.set_text(text);
+        // enter validates with the default button
+        entry.set_activates_default(true);
+        if let Some(area) = dialog
+            .get_message_area()
+            .and_then(|area| area.downcast::<gtk::Box>().ok())
+        {
+            area.pack_start(&entry, false, false, 0);
+        }
+        entry
+    });
+
+    (dialog, entry)
+}
+
+#[cfg(target_os = "linux")]
+fn clicked_button(response: ResponseType) -> Option<usize> {
+    match response {
+        ResponseType::Other(index) => Some(index as usize),
+        _ => None,
+    }
+}
@@ -1,15 +1,30 @@
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    env::var,
+    collections::{HashMap, HashSet},
     fs::File,
     io::BufReader,
     io::Cursor,
+    io::{Read, Seek, SeekFrom, Write},
+    ops::Range,
     path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 use thiserror::Error;
 use walkdir::WalkDir;
 
+use crate::asset_cache::{content_hash, AssetCache};
+use crate::assets::Assets;
+use crate::helpers::{from_hex, to_hex};
+use crate::protocol::mime_type;
+use rayon::prelude::*;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct AssetKey(String);
 
@@ -68,11 +83,174 @@ impl<P: AsRef<Path>> From<P> for AssetKey {
     }
 }
 
-/// (key, (original filepath, compressed bytes))
-type Asset = (AssetKey, (String, Vec<u8>));
+/// (key, asset, how the bytes are stored, stored bytes), the bytes are
+/// `None` for copies of a content already stored the same way.
+type Asset = (AssetKey, EmbeddedAsset, Compression, Option<Vec<u8>>);
+
+/// Media are played with range requests, keep them seekable.
+const RAW_MIME_PREFIXES: &[&str] = &["audio/", "video/"];
+
+/// Files larger than this are stored raw so we never decompress them entirely.
+const RAW_SIZE_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Formats already compressed, zstd would only waste time on them.
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "webp", "avif", "ico", "woff", "woff2", "mp3", "mp4", "m4v",
+    "webm", "ogg", "ogv", "oga", "flac", "zip", "gz", "br", "zst", "xz", "7z", "pdf",
+];
+
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 19;
+
+/// How assets are stored in the bundle.
+#[derive(Debug, Clone)]
+pub struct CompressionOptions {
+    /// zstd level, from 1 to 22
+    pub level: i32,
+    /// store every asset raw
+    pub disabled: bool,
+    /// extra extensions stored raw
+    pub raw_extensions: Vec<String>,
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self {
+            level: DEFAULT_COMPRESSION_LEVEL,
+            disabled: false,
+            raw_extensions: Vec::new(),
+        }
+    }
+}
+
+impl CompressionOptions {
+    fn compression_for(&self, path: &Path, mime_type: &str, size: u64) -> Compression {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let already_compressed = COMPRESSED_EXTENSIONS.contains(&extension.as_str())
+            || self
+                .raw_extensions
+                .iter()
+                .any(|raw| raw.trim_start_matches('.').eq_ignore_ascii_case(&extension));
+
+        if self.disabled
+            || already_compressed
+            || size > RAW_SIZE_THRESHOLD
+            || RAW_MIME_PREFIXES
+                .iter()
+                .any(|prefix| mime_type.starts_with(prefix))
+        {
+            Compression::None
+        } else {
+            Compression::Zstd
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Compression {
+    None,
+    Zstd,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmbeddedAsset {
+    /// MIME type guessed from the extension at compile time
+    mime_type: String,
+    /// uncompressed size
+    size: u64,
+    /// content hash, identical files share the same blob
+    hash: String,
+}
+
+/// Stored bytes of an asset content.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Blob {
+    compression: Compression,
+    /// position of the stored bytes in the blob section
+    offset: u64,
+    length: u64,
+}
+
+/// Blobs encrypted with ChaCha20-Poly1305 at compile time.
+///
+/// The key ships next to the assets so this only keeps the content from
+/// being extracted with a zstd decoder, it is not a protection against
+/// someone reading our code.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Encryption {
+    /// hex encoded
+    key: String,
+}
+
+impl Encryption {
+    fn key(&self) -> Option<LessSafeKey> {
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &from_hex(&self.key)?).ok()?;
+        Some(LessSafeKey::new(key))
+    }
+}
+
+/// Blob offsets are unique within a bundle and the key is generated for
+/// each build, which makes them a valid nonce.
+fn blob_nonce(offset: u64) -> Nonce {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[NONCE_LEN - 8..].copy_from_slice(&offset.to_be_bytes());
+    Nonce::assume_unique_for_key(nonce)
+}
+
+/// Where the stored bytes of our assets live.
+#[derive(Debug, Clone)]
+enum Storage {
+    /// compressed at compile time
+    Memory(Arc<Vec<u8>>),
+    /// appended to an executable, read on demand
+    File { path: PathBuf, offset: u64 },
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage::Memory(Default::default())
+    }
+}
+
+impl Storage {
+    fn read(&self, offset: u64, length: u64) -> std::io::Result<Box<dyn Read + Send>> {
+        match self {
+            Storage::Memory(data) => {
+                let start = offset as usize;
+                let end = start + length as usize;
+                let bytes = data.get(start..end).ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "asset out of bounds")
+                })?;
+                Ok(Box::new(Cursor::new(bytes.to_vec())))
+            }
+            Storage::File {
+                path,
+                offset: blobs,
+            } => {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(blobs + offset))?;
+                Ok(Box::new(file.take(length)))
+            }
+        }
+    }
+}
 
+/// Bundle layout: `[index length: u64][index json][blobs]`, the index only
+/// holds paths relative to the asset root.
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct EmbeddedAssets(HashMap<AssetKey, (String, Vec<u8>)>);
+pub struct EmbeddedAssets {
+    assets: HashMap<AssetKey, EmbeddedAsset>,
+    /// content hash -> stored bytes
+    blobs: HashMap<String, Blob>,
+    #[serde(default)]
+    encryption: Option<Encryption>,
+    #[serde(skip)]
+    storage: Storage,
+}
 
 /// All possible errors while reading and compressing an [`EmbeddedAssets`] directory
 #[derive(Debug, Error)]
@@ -92,6 +270,12 @@ pub enum EmbeddedAssetsError {
     #[error("invalid prefix {prefix} used while including path {path}")]
     PrefixInvalid { prefix: PathBuf, path: PathBuf },
 
+    #[error("failed to write asset cache {path} because {error}")]
+    Cache {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+
     #[error("failed to walk directory {path} because {error}")]
     Walkdir {
         path: PathBuf,
@@ -99,32 +283,89 @@ pub enum EmbeddedAssetsError {
     },
 }
 
-pub trait Assets {
-    /// Get the content of the passed [`AssetKey`].
-    fn get(&self, key: &str) -> Option<Vec<u8>>;
-}
-
 impl Assets for EmbeddedAssets {
     fn get(&self, key: &str) -> Option<Vec<u8>> {
-        println!("SEARCH FOR {}", key);
-        let (_, vec) = self.0.get(&AssetKey::from(String::from(key)))?;
-        let decoded_vec = zstd::decode_all(Cursor::new(vec)).unwrap();
-        Some(decoded_vec)
+        let (asset, blob) = self.asset(key)?;
+        let reader = self.read_blob(blob)?;
+        match blob.compression {
+            Compression::None => {
+                let mut bytes = Vec::with_capacity(asset.size as usize);
+                let mut reader = reader;
+                reader.read_to_end(&mut bytes).ok()?;
+                Some(bytes)
+            }
+            Compression::Zstd => zstd::decode_all(reader).ok(),
+        }
+    }
+
+    fn mime_type(&self, key: &str) -> Option<String> {
+        self.assets
+            .get(&AssetKey::from(String::from(key)))
+            .map(|asset| asset.mime_type.clone())
+    }
+
+    fn size(&self, key: &str) -> Option<u64> {
+        self.assets
+            .get(&AssetKey::from(String::from(key)))
+            .map(|asset| asset.size)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.assets
+            .keys()
+            .map(|key| key.as_ref().trim_start_matches('/').to_string())
+            .collect()
+    }
+
+    fn open(&self, key: &str) -> Option<Box<dyn Read + Send>> {
+        let (asset, blob) = self.asset(key)?;
+        match (blob.compression, &self.encryption) {
+            // raw assets are read straight from the storage
+            (Compression::None, None) => self.storage.read(blob.offset, asset.size).ok(),
+            // encrypted blobs are authenticated as a whole before being read
+            _ => Some(Box::new(Cursor::new(self.get(key)?))),
+        }
+    }
+
+    fn read_range(&self, key: &str, range: Range<u64>) -> Option<Box<dyn Read + Send>> {
+        let (asset, blob) = self.asset(key)?;
+        let end = range.end.min(asset.size);
+        let start = range.start.min(end);
+        match (blob.compression, &self.encryption) {
+            // raw assets are read straight from the storage
+            (Compression::None, None) => self.storage.read(blob.offset + start, end - start).ok(),
+            _ => {
+                let bytes = self.get(key)?;
+                Some(Box::new(Cursor::new(
+                    bytes[start as usize..end as usize].to_vec(),
+                )))
+            }
+        }
     }
 }
 
 impl EmbeddedAssets {
     /// Compress a directory of assets, ready to be generated into a [`tauri_api::assets::Assets`].
-    pub fn new(path: &Path) -> Result<Self, EmbeddedAssetsError> {
-        WalkDir::new(&path)
+    ///
+    /// Files are compressed in parallel, unchanged files are taken from the
+    /// cache when one is given.
+    pub fn new(
+        path: &Path,
+        options: &CompressionOptions,
+        cache: Option<&AssetCache>,
+    ) -> Result<Self, EmbeddedAssetsError> {
+        let entries = WalkDir::new(&path)
             .follow_links(true)
             .into_iter()
+            // never embed our own cache
+            .filter_entry(|entry| {
+                cache.map_or(true, |cache| !entry.path().starts_with(cache.dir()))
+            })
             .filter_map(|entry| match entry {
                 // we only serve files, not directory listings
                 Ok(entry) if entry.file_type().is_dir() => None,
 
-                // compress all files encountered
-                Ok(entry) => Some(Self::compress_file(path, entry.path())),
+                Ok(entry) => Some(Ok(entry.into_path())),
 
                 // pass down error through filter to fail when encountering any error
                 Err(error) => Some(Err(EmbeddedAssetsError::Walkdir {
@@ -132,34 +373,298 @@ impl EmbeddedAssets {
                     error,
                 })),
             })
-            .collect::<Result<_, _>>()
-            .map(Self)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // compress all files encountered
+        let total = entries.len();
+        let done = AtomicUsize::new(0);
+        let stored = Mutex::new(HashSet::new());
+        let mut files = entries
+            .par_iter()
+            .map(|entry| {
+                let asset = Self::compress_file(path, entry, options, cache, &stored);
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                eprint!("\rEmbedding assets {}/{}", done, total);
+                asset
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        eprintln!();
+
+        if let Some(cache) = cache {
+            cache.save().map_err(|error| EmbeddedAssetsError::Cache {
+                path: cache.dir().to_owned(),
+                error,
+            })?;
+        }
+
+        // pack all stored bytes in a single blob section, in a stable order,
+        // identical files are only stored once. When their compression differ
+        // the raw copy wins so the content stays seekable.
+        files.sort_by(|(a, _, a_compression, _), (b, _, b_compression, _)| {
+            (*a_compression != Compression::None, a).cmp(&(*b_compression != Compression::None, b))
+        });
+        let mut data = Vec::new();
+        let mut assets = HashMap::new();
+        let mut blobs = HashMap::new();
+        for (key, asset, compression, bytes) in files {
+            if let (Some(mut bytes), false) = (bytes, blobs.contains_key(&asset.hash)) {
+                let blob = Blob {
+                    compression,
+                    offset: data.len() as u64,
+                    length: bytes.len() as u64,
+                };
+                data.append(&mut bytes);
+                blobs.insert(asset.hash.clone(), blob);
+            }
+            assets.insert(key, asset);
+        }
+
+        Ok(Self {
+            assets,
+            blobs,
+            encryption: None,
+            storage: Storage::Memory(Arc::new(data)),
+        })
+    }
+
+    /// Encrypt every blob with a key generated for this build.
+    pub fn encrypt(&mut self) -> crate::Result<()> {
+        let data = match &self.storage {
+            Storage::Memory(data) => data.clone(),
+            Storage::File { .. } => anyhow::bail!("Assets are already bundled"),
+        };
+        if self.encryption.is_some() {
+            anyhow::bail!("Assets are already encrypted");
+        }
+
+        let mut key = [0; 32];
+        SystemRandom::new()
+            .fill(&mut key)
+            .map_err(|_| anyhow::anyhow!("Unable to generate an encryption key"))?;
+        let encryption = Encryption { key: to_hex(&key) };
+        let sealing_key = encryption
+            .key()
+            .ok_or_else(|| anyhow::anyhow!("Invalid encryption key"))?;
+
+        // keep the blobs in the same order in the new blob section
+        let mut blobs: Vec<&mut Blob> = self.blobs.values_mut().collect();
+        blobs.sort_by_key(|blob| blob.offset);
+
+        let mut encrypted = Vec::with_capacity(data.len());
+        for blob in blobs {
+            let start = blob.offset as usize;
+            let mut bytes = data[start..start + blob.length as usize].to_vec();
+            blob.offset = encrypted.len() as u64;
+            sealing_key
+                .seal_in_place_append_tag(blob_nonce(blob.offset), Aad::empty(), &mut bytes)
+                .map_err(|_| anyhow::anyhow!("Unable to encrypt assets"))?;
+            blob.length = bytes.len() as u64;
+            encrypted.append(&mut bytes);
+        }
+
+        self.storage = Storage::Memory(Arc::new(encrypted));
+        self.encryption = Some(encryption);
+        Ok(())
+    }
+
+    fn asset(&self, key: &str) -> Option<(&EmbeddedAsset, &Blob)> {
+        let asset = self.assets.get(&AssetKey::from(String::from(key)))?;
+        Some((asset, self.blobs.get(&asset.hash)?))
+    }
+
+    /// Stored bytes of a blob, decrypted when needed.
+    fn read_blob(&self, blob: &Blob) -> Option<Box<dyn Read + Send>> {
+        let mut reader = self.storage.read(blob.offset, blob.length).ok()?;
+        let encryption = match &self.encryption {
+            Some(encryption) => encryption,
+            None => return Some(reader),
+        };
+
+        let mut bytes = Vec::with_capacity(blob.length as usize);
+        reader.read_to_end(&mut bytes).ok()?;
+        let length = encryption
+            .key()?
+            .open_in_place(blob_nonce(blob.offset), Aad::empty(), &mut bytes)
+            .ok()?
+            .len();
+        bytes.truncate(length);
+        Some(Box::new(Cursor::new(bytes)))
     }
 
-    /// Use highest compression level for release, the fastest one for everything else
-    fn compression_level() -> i32 {
-        match var("PROFILE").as_ref().map(String::as_str) {
-            Ok("release") => 22,
-            _ => -5,
+    /// Serialize the assets to be appended to a binary.
+    pub fn to_bundle(&self) -> crate::Result<Vec<u8>> {
+        let blobs = match &self.storage {
+            Storage::Memory(blobs) => blobs,
+            Storage::File { .. } => anyhow::bail!("Assets are already bundled"),
+        };
+
+        let index = serde_json::to_vec(&self)?;
+        let mut bundle = Vec::with_capacity(8 + index.len() + blobs.len());
+        bundle.write_all(&(index.len() as u64).to_be_bytes())?;
+        bundle.write_all(&index)?;
+        bundle.write_all(blobs)?;
+        Ok(bundle)
+    }
+
+    /// Read the index of a bundle of `bundle_len` bytes appended to the given
+    /// file, asset content is read on demand.
+    pub fn from_bundle(path: &Path, bundle_pos: u64, bundle_len: u64) -> crate::Result<Self> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(bundle_pos))?;
+
+        let mut index_len = [0; 8];
+        file.read_exact(&mut index_len)?;
+        let index_len = u64::from_be_bytes(index_len);
+        if index_len > bundle_len.saturating_sub(8) {
+            anyhow::bail!("Bundle index is larger than the bundle");
+        }
+
+        let mut index = Vec::with_capacity(index_len as usize);
+        (&mut file).take(index_len).read_to_end(&mut index)?;
+
+        let mut assets: Self = serde_json::from_slice(&index)?;
+        assets.storage = Storage::File {
+            path: path.to_owned(),
+            offset: bundle_pos + 8 + index_len,
+        };
+        Ok(assets)
+    }
+
+    /// Print the stored size of every asset.
+    pub fn report(&self) {
+        let mut keys: Vec<&AssetKey> = self.assets.keys().collect();
+        keys.sort();
+
+        let mut total_size = 0;
+        println!(
+            "{:<48} {:>12} {:>12} {:>7}  compression",
+            "asset", "size", "stored", "ratio"
+        );
+        for key in keys {
+            let (asset, blob) = match self.asset(key.as_ref()) {
+                Some(asset) => asset,
+                None => continue,
+            };
+            total_size += asset.size;
+            println!(
+                "{:<48} {:>12} {:>12} {:>6.1}%  {:?}",
+                key.as_ref(),
+                format_size(asset.size),
+                format_size(blob.length),
+                ratio(blob.length, asset.size),
+                blob.compression,
+            );
         }
+
+        // shared blobs are only counted once
+        let total_stored = self.blobs.values().map(|blob| blob.length).sum();
+        let duplicates = self.assets.len() - self.blobs.len();
+        if duplicates > 0 {
+            println!("{} duplicated assets stored once", duplicates);
+        }
+        println!(
+            "{:<48} {:>12} {:>12} {:>6.1}%",
+            format!("total ({} assets)", self.assets.len()),
+            format_size(total_size),
+            format_size(total_stored),
+            ratio(total_stored, total_size),
+        );
+    }
+
+    fn compress(path: &Path, content: &[u8], level: i32) -> Result<Vec<u8>, EmbeddedAssetsError> {
+        zstd::encode_all(content, level).map_err(|error| EmbeddedAssetsError::AssetWrite {
+            path: path.to_owned(),
+            error,
+        })
     }
 
     /// Compress a file and spit out the information in a [`HashMap`] friendly form.
-    fn compress_file(prefix: &Path, path: &Path) -> Result<Asset, EmbeddedAssetsError> {
-        let reader = File::open(&path).map(BufReader::new).map_err(|error| {
-            EmbeddedAssetsError::AssetRead {
-                path: path.to_owned(),
-                error,
+    ///
+    /// `stored` holds the contents already taken care of by another file,
+    /// copies are only hashed.
+    fn compress_file(
+        prefix: &Path,
+        path: &Path,
+        options: &CompressionOptions,
+        cache: Option<&AssetCache>,
+        stored: &Mutex<HashSet<(String, Compression)>>,
+    ) -> Result<Asset, EmbeddedAssetsError> {
+        let read_error = |error| EmbeddedAssetsError::AssetRead {
+            path: path.to_owned(),
+            error,
+        };
+
+        let mime_type = mime_type(&path.to_string_lossy()).to_string();
+        let metadata = std::fs::metadata(&path).map_err(read_error)?;
+        let size = metadata.len();
+
+        let compression = options.compression_for(path, &mime_type, size);
+        let variant = format!("zstd{}", options.level);
+
+        // whoever claims a content first has to provide its bytes
+        let claim = |hash: &str| {
+            stored
+                .lock()
+                .unwrap()
+                .insert((hash.to_string(), compression))
+        };
+        let claimed = |hash: &str| {
+            stored
+                .lock()
+                .unwrap()
+                .contains(&(hash.to_string(), compression))
+        };
+
+        // unchanged since the last build, we don't even need to read it
+        let cached = match (compression, cache) {
+            (Compression::Zstd, Some(cache)) => {
+                cache.cached_hash(path, &metadata).and_then(|hash| {
+                    if claimed(&hash) {
+                        return Some((None, hash));
+                    }
+                    let bytes = cache.get(&hash, &variant)?;
+                    claim(&hash);
+                    Some((Some(bytes), hash))
+                })
             }
-        })?;
+            _ => None,
+        };
 
-        // entirely read compressed asset into bytes
-        let bytes = zstd::encode_all(reader, Self::compression_level()).map_err(|error| {
-            EmbeddedAssetsError::AssetWrite {
-                path: path.to_owned(),
-                error,
+        let (bytes, hash) = match cached {
+            Some(cached) => cached,
+            None => {
+                // entirely read asset into bytes, compressed when needed
+                let mut content = Vec::with_capacity(size as usize);
+                File::open(&path)
+                    .map(BufReader::new)
+                    .and_then(|mut reader| reader.read_to_end(&mut content))
+                    .map_err(read_error)?;
+
+                let hash = content_hash(&content);
+                if let Some(cache) = cache {
+                    cache.record(path, &metadata, &hash);
+                }
+
+                let bytes = match (claim(&hash), compression, cache) {
+                    // a copy of a content already stored, only its hash is needed
+                    (false, ..) => None,
+                    (true, Compression::None, _) => Some(content),
+                    (true, Compression::Zstd, None) => {
+                        Some(Self::compress(path, &content, options.level)?)
+                    }
+                    (true, Compression::Zstd, Some(cache)) => match cache.get(&hash, &variant) {
+                        Some(bytes) => Some(bytes),
+                        None => {
+                            let bytes = Self::compress(path, &content, options.level)?;
+                            // the cache is best effort, a failed write only costs time
+                            let _ = cache.insert(&hash, &variant, &bytes);
+                            Some(bytes)
+                        }
+                    },
+                };
+                (bytes, hash)
             }
-        })?;
+        };
 
         // get a key to the asset path without the asset directory prefix
         let key = path
@@ -170,6 +675,305 @@ impl EmbeddedAssets {
                 path: path.to_owned(),
             })?;
 
-        Ok((key, (path.display().to_string(), bytes)))
+        let asset = EmbeddedAsset {
+            mime_type,
+            size,
+            hash,
+        };
+
+        Ok((key, asset, compression, bytes))
+    }
+}
+
+fn ratio(stored: u64, size: u64) -> f64 {
+    if size == 0 {
+        100.0
+    } else {
+        stored as f64 * 100.0 / size as f64
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    /// Asset directory with a compressed page, a raw video and a copy of
+    /// the page.
+    fn fixture(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wry-embed-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("pages")).unwrap();
+        std::fs::write(dir.join("index.html"), "<h1>hello</h1>".repeat(100)).unwrap();
+        std::fs::write(dir.join("pages/copy.html"), "<h1>hello</h1>".repeat(100)).unwrap();
+        std::fs::write(dir.join("video.mp4"), vec![7; 4096]).unwrap();
+        dir
+    }
+
+    fn embed(dir: &Path) -> EmbeddedAssets {
+        EmbeddedAssets::new(dir, &CompressionOptions::default(), None).unwrap()
+    }
+
+    fn read(assets: &EmbeddedAssets, key: &str) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        assets.open(key)?.read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    /// Write the bundle after some bytes, like at the end of a binary.
+    fn bundle(assets: &EmbeddedAssets, dir: &Path) -> EmbeddedAssets {
+        let bundle = assets.to_bundle().unwrap();
+        let path = dir.join("bundle");
+        let mut file = b"runtime".to_vec();
+        file.extend_from_slice(&bundle);
+        std::fs::write(&path, file).unwrap();
+        EmbeddedAssets::from_bundle(&path, 7, bundle.len() as u64).unwrap()
+    }
+
+    fn flip_first_byte(assets: &mut EmbeddedAssets, key: &str) {
+        let offset = assets.asset(key).unwrap().1.offset as usize;
+        match &mut assets.storage {
+            Storage::Memory(data) => Arc::make_mut(data)[offset] ^= 1,
+            Storage::File { .. } => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn text_is_compressed() {
+        let options = CompressionOptions::default();
+        let compression =
+            |path: &str, mime_type| options.compression_for(Path::new(path), mime_type, 1024);
+        assert_eq!(compression("index.html", "text/html"), Compression::Zstd);
+        assert_eq!(compression("app.js", "text/javascript"), Compression::Zstd);
+        assert_eq!(
+            compression("README", "application/octet-stream"),
+            Compression::Zstd
+        );
+    }
+
+    #[test]
+    fn compressed_formats_and_media_are_stored_raw() {
+        let options = CompressionOptions::default();
+        let compression =
+            |path: &str, mime_type| options.compression_for(Path::new(path), mime_type, 1024);
+        assert_eq!(compression("logo.png", "image/png"), Compression::None);
+        assert_eq!(compression("LOGO.PNG", "image/png"), Compression::None);
+        assert_eq!(compression("font.woff2", "font/woff2"), Compression::None);
+        // media stay seekable whatever their extension
+        assert_eq!(
+            compression("clip.mov", "video/quicktime"),
+            Compression::None
+        );
+        assert_eq!(compression("sound.wav", "audio/wav"), Compression::None);
+    }
+
+    #[test]
+    fn raw_extensions_are_stored_raw() {
+        let options = CompressionOptions {
+            raw_extensions: vec!["wasm".into(), ".DAT".into()],
+            ..Default::default()
+        };
+        let compression =
+            |path: &str| options.compression_for(Path::new(path), "application/octet-stream", 1024);
+        assert_eq!(compression("app.wasm"), Compression::None);
+        assert_eq!(compression("APP.WASM"), Compression::None);
+        assert_eq!(compression("level.dat"), Compression::None);
+        assert_eq!(compression("level.bin"), Compression::Zstd);
+        // an extension, not a suffix
+        assert_eq!(compression("wasm"), Compression::Zstd);
+    }
+
+    #[test]
+    fn large_files_are_stored_raw() {
+        let options = CompressionOptions::default();
+        let path = Path::new("data.json");
+        assert_eq!(
+            options.compression_for(path, "application/json", RAW_SIZE_THRESHOLD),
+            Compression::Zstd
+        );
+        assert_eq!(
+            options.compression_for(path, "application/json", RAW_SIZE_THRESHOLD + 1),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn disabled_compression_stores_everything_raw() {
+        let options = CompressionOptions {
+            disabled: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            options.compression_for(Path::new("index.html"), "text/html", 1024),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn copies_are_compressed_once() {
+        let dir = fixture("once");
+        let options = CompressionOptions::default();
+        let stored = Mutex::new(HashSet::new());
+        let compress = |path: &str| {
+            EmbeddedAssets::compress_file(&dir, &dir.join(path), &options, None, &stored).unwrap()
+        };
+        let (_, page, compression, bytes) = compress("index.html");
+        assert_eq!(compression, Compression::Zstd);
+        assert!(bytes.is_some());
+        let (_, copy, _, bytes) = compress("pages/copy.html");
+        assert_eq!(copy.hash, page.hash);
+        assert_eq!(bytes, None);
+
+        // a copy stored another way still needs its own bytes
+        let raw = CompressionOptions {
+            disabled: true,
+            ..Default::default()
+        };
+        let (_, _, compression, bytes) =
+            EmbeddedAssets::compress_file(&dir, &dir.join("pages/copy.html"), &raw, None, &stored)
+                .unwrap();
+        assert_eq!(compression, Compression::None);
+        assert!(bytes.is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn read_range(assets: &EmbeddedAssets, key: &str, range: Range<u64>) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        assets
+            .read_range(key, range)?
+            .read_to_end(&mut bytes)
+            .ok()?;
+        Some(bytes)
+    }
+
+    #[test]
+    fn ranges_are_read_from_raw_and_compressed_blobs() {
+        let dir = fixture("range");
+        let page = std::fs::read(dir.join("index.html")).unwrap();
+        let assets = embed(&dir);
+        // the video is stored raw, the page compressed
+        assert_eq!(read_range(&assets, "video.mp4", 10..20), Some(vec![7; 10]));
+        assert_eq!(
+            read_range(&assets, "video.mp4", 4090..5000),
+            Some(vec![7; 6])
+        );
+        assert_eq!(
+            read_range(&assets, "index.html", 4..18),
+            Some(page[4..18].to_vec())
+        );
+
+        let bundled = bundle(&assets, &dir);
+        assert_eq!(read_range(&bundled, "video.mp4", 0..3), Some(vec![7; 3]));
+        assert_eq!(
+            read_range(&bundled, "index.html", 14..28),
+            Some(page[14..28].to_vec())
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn identical_files_share_a_blob() {
+        let dir = fixture("dedup");
+        let assets = embed(&dir);
+        assert_eq!(assets.assets.len(), 3);
+        assert_eq!(assets.blobs.len(), 2);
+        let (page, page_blob) = assets.asset("index.html").unwrap();
+        let (copy, copy_blob) = assets.asset("pages/copy.html").unwrap();
+        assert_eq!(page.hash, copy.hash);
+        assert_eq!(page_blob.offset, copy_blob.offset);
+        assert_eq!(assets.get("pages/copy.html"), assets.get("index.html"));
+
+        let data = match &assets.storage {
+            Storage::Memory(data) => data.len() as u64,
+            Storage::File { .. } => unreachable!(),
+        };
+        let stored: u64 = assets.blobs.values().map(|blob| blob.length).sum();
+        assert_eq!(data, stored);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bundle_index_has_no_build_paths() {
+        let dir = fixture("paths");
+        let bundle = embed(&dir).to_bundle().unwrap();
+        let index_len = u64::from_be_bytes(bundle[..8].try_into().unwrap()) as usize;
+        let index = String::from_utf8(bundle[8..8 + index_len].to_vec()).unwrap();
+        assert!(!index.contains(&*dir.to_string_lossy()));
+        assert!(!index.contains(&*std::env::temp_dir().to_string_lossy()));
+
+        let index: serde_json::Value = serde_json::from_str(&index).unwrap();
+        let mut keys: Vec<&String> = index["assets"].as_object().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["/index.html", "/pages/copy.html", "/video.mp4"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_assets_round_trip() {
+        let dir = fixture("encrypt");
+        let page = std::fs::read(dir.join("index.html")).unwrap();
+        let video = std::fs::read(dir.join("video.mp4")).unwrap();
+        let mut assets = embed(&dir);
+        assets.encrypt().unwrap();
+
+        assert_eq!(assets.get("index.html"), Some(page.clone()));
+        assert_eq!(assets.get("pages/copy.html"), Some(page.clone()));
+        assert_eq!(assets.get("video.mp4"), Some(video.clone()));
+        assert_eq!(read(&assets, "index.html"), Some(page.clone()));
+        assert_eq!(read(&assets, "video.mp4"), Some(video.clone()));
+
+        let bundled = bundle(&assets, &dir);
+        assert_eq!(bundled.get("index.html"), Some(page.clone()));
+        assert_eq!(read(&bundled, "video.mp4"), Some(video));
+        assert_eq!(bundled.mime_type("index.html"), Some("text/html".into()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_assets_are_not_stored_in_clear() {
+        let dir = fixture("clear");
+        let mut assets = embed(&dir);
+        assets.encrypt().unwrap();
+        let bundle = assets.to_bundle().unwrap();
+        assert!(!bundle.windows(64).any(|window| window == &[7; 64][..]));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_refused() {
+        let dir = fixture("tamper");
+        let mut assets = embed(&dir);
+        assets.encrypt().unwrap();
+        flip_first_byte(&mut assets, "video.mp4");
+        assert_eq!(assets.get("video.mp4"), None);
+        assert!(assets.open("video.mp4").is_none());
+        // other blobs are still fine
+        assert!(assets.get("index.html").is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn assets_are_encrypted_once() {
+        let dir = fixture("twice");
+        let mut assets = embed(&dir);
+        assets.encrypt().unwrap();
+        assert!(assets.encrypt().is_err());
+        assert!(bundle(&assets, &dir).encrypt().is_err());
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -1,4 +1,6 @@
 use deno_core::serde::Serialize;
+use std::path::PathBuf;
+use wry::webview::RpcRequest;
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase", tag = "event")]
@@ -6,22 +8,466 @@ pub enum Event {
     WindowCreated,
     DomContentLoaded,
     Undefined,
+    /// the user asked to close the window, it stays open until `wry_close`
+    CloseRequested,
     Close,
     Suspended,
     Resumed,
+    Resized {
+        width: u32,
+        height: u32,
+    },
+    Moved {
+        x: i32,
+        y: i32,
+    },
+    Focused,
+    Blurred,
+    #[serde(rename_all = "camelCase")]
+    ScaleFactorChanged {
+        scale_factor: f64,
+    },
+    Minimized,
+    Restored,
+    FileHovered {
+        paths: Vec<PathBuf>,
+    },
+    FileDropped {
+        paths: Vec<PathBuf>,
+    },
+    FileHoverCancelled,
+    ThemeChanged {
+        theme: Theme,
+    },
+    /// `key` is the unshifted key: `a`, `1` and `,` for printable keys, `Enter`, `Space`,
+    /// `ArrowUp`, `Shift` or `F1` for the others
+    KeyboardInput {
+        key: Option<String>,
+        scancode: u32,
+        state: ElementState,
+        modifiers: Modifiers,
+    },
+    MouseInput {
+        button: MouseButton,
+        state: ElementState,
+        modifiers: Modifiers,
+    },
+    /// positive deltas scroll down and right, like DOM wheel events
+    #[serde(rename_all = "camelCase")]
+    MouseWheel {
+        delta_x: f64,
+        delta_y: f64,
+    },
+    Load,
+    BeforeUnload,
+    NavigationStarted {
+        url: String,
+    },
+    NavigationFinished {
+        url: String,
+    },
+    TitleChanged {
+        title: String,
+    },
+    PageCrashed,
+    NavigationBlocked {
+        url: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    ProtocolRequest {
+        request_id: u64,
+        scheme: String,
+        url: String,
+        path: String,
+    },
+    ConsoleMessage {
+        level: String,
+        message: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    DialogResponse {
+        request_id: u64,
+        result: serde_json::Value,
+    },
+    MenuItemClicked {
+        id: String,
+        /// state of checkbox items after the click
+        #[serde(skip_serializing_if = "Option::is_none")]
+        checked: Option<bool>,
+    },
+    TrayClicked,
+    /// the app was opened with a URL of its schemes
+    OpenUrl {
+        url: String,
+    },
+    /// the app was launched again in single instance mode
+    SecondInstance {
+        args: Vec<String>,
+        cwd: Option<PathBuf>,
+    },
+    /// registered with `wry_register_shortcut`
+    Shortcut {
+        accelerator: String,
+    },
+    /// `action` is null when the notification itself was clicked
+    #[serde(rename_all = "camelCase")]
+    NotificationClicked {
+        notification_id: u32,
+        action: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    NotificationClosed {
+        notification_id: u32,
+        reason: String,
+    },
+}
+
+impl Event {
+    /// Map a RPC request sent by our injected scripts to an event.
+    pub fn from_rpc(req: &RpcRequest) -> Option<Self> {
+        // scripts send a single object as parameter
+        let param = |key: &str| {
+            req.params
+                .as_ref()
+                .and_then(|params| params.get(0))
+                .and_then(|param| param.get(key))
+                .and_then(|value| value.as_str())
+                .map(String::from)
+        };
+
+        match req.method.as_str() {
+            "domContentLoaded" => Some(Event::DomContentLoaded),
+            "load" => Some(Event::Load),
+            "beforeUnload" => Some(Event::BeforeUnload),
+            "navigationStarted" => Some(Event::NavigationStarted { url: param("url")? }),
+            "navigationFinished" => Some(Event::NavigationFinished { url: param("url")? }),
+            "navigationBlocked" => Some(Event::NavigationBlocked { url: param("url")? }),
+            "titleChanged" => Some(Event::TitleChanged {
+                title: param("title")?,
+            }),
+            "consoleMessage" => Some(Event::ConsoleMessage {
+                level: param("level")?,
+                message: param("message")?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ElementState {
+    Pressed,
+    Released,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u16),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+#[cfg(not(target_os = "linux"))]
+impl From<winit::event::ElementState> for ElementState {
+    fn from(state: winit::event::ElementState) -> Self {
+        match state {
+            winit::event::ElementState::Pressed => ElementState::Pressed,
+            winit::event::ElementState::Released => ElementState::Released,
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl From<winit::event::MouseButton> for MouseButton {
+    fn from(button: winit::event::MouseButton) -> Self {
+        match button {
+            winit::event::MouseButton::Left => MouseButton::Left,
+            winit::event::MouseButton::Right => MouseButton::Right,
+            winit::event::MouseButton::Middle => MouseButton::Middle,
+            winit::event::MouseButton::Other(button) => MouseButton::Other(button),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl From<winit::event::ModifiersState> for Modifiers {
+    fn from(modifiers: winit::event::ModifiersState) -> Self {
+        Modifiers {
+            shift: modifiers.shift(),
+            ctrl: modifiers.ctrl(),
+            alt: modifiers.alt(),
+            meta: modifiers.logo(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl From<winit::window::Theme> for Theme {
+    fn from(theme: winit::window::Theme) -> Self {
+        match theme {
+            winit::window::Theme::Light => Theme::Light,
+            winit::window::Theme::Dark => Theme::Dark,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<gdk::ModifierType> for Modifiers {
+    fn from(state: gdk::ModifierType) -> Self {
+        Modifiers {
+            shift: state.contains(gdk::ModifierType::SHIFT_MASK),
+            ctrl: state.contains(gdk::ModifierType::CONTROL_MASK),
+            alt: state.contains(gdk::ModifierType::MOD1_MASK),
+            meta: state.contains(gdk::ModifierType::SUPER_MASK)
+                || state.contains(gdk::ModifierType::META_MASK),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<u32> for MouseButton {
+    fn from(button: u32) -> Self {
+        match button {
+            1 => MouseButton::Left,
+            2 => MouseButton::Middle,
+            3 => MouseButton::Right,
+            other => MouseButton::Other(other as u16),
+        }
+    }
 }
 
 #[cfg(not(target_os = "linux"))]
 impl From<winit::event::Event<'_, ()>> for Event {
+    #[allow(deprecated)]
     fn from(event: winit::event::Event<()>) -> Self {
         match event {
             winit::event::Event::Suspended => Event::Suspended,
             winit::event::Event::Resumed => Event::Resumed,
-            winit::event::Event::WindowEvent {
-                event: winit::event::WindowEvent::CloseRequested,
-                ..
-            } => Event::Close,
+            winit::event::Event::WindowEvent { event, .. } => match event {
+                winit::event::WindowEvent::CloseRequested => Event::CloseRequested,
+                // windows reports a zero sized window when minimized
+                winit::event::WindowEvent::Resized(size) if size.width == 0 && size.height == 0 => {
+                    Event::Minimized
+                }
+                winit::event::WindowEvent::Resized(size) => Event::Resized {
+                    width: size.width,
+                    height: size.height,
+                },
+                winit::event::WindowEvent::Moved(position) => Event::Moved {
+                    x: position.x,
+                    y: position.y,
+                },
+                winit::event::WindowEvent::Focused(true) => Event::Focused,
+                winit::event::WindowEvent::Focused(false) => Event::Blurred,
+                winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    Event::ScaleFactorChanged { scale_factor }
+                }
+                winit::event::WindowEvent::ThemeChanged(theme) => Event::ThemeChanged {
+                    theme: theme.into(),
+                },
+                winit::event::WindowEvent::KeyboardInput { input, .. } => Event::KeyboardInput {
+                    key: input.virtual_keycode.map(key_name),
+                    scancode: input.scancode,
+                    state: input.state.into(),
+                    modifiers: input.modifiers.into(),
+                },
+                winit::event::WindowEvent::MouseInput {
+                    button,
+                    state,
+                    modifiers,
+                    ..
+                } => Event::MouseInput {
+                    button: button.into(),
+                    state: state.into(),
+                    modifiers: modifiers.into(),
+                },
+                winit::event::WindowEvent::MouseWheel { delta, .. } => {
+                    // winit reports scrolling up as a positive delta
+                    let (delta_x, delta_y) = match delta {
+                        winit::event::MouseScrollDelta::LineDelta(x, y) => (x as f64, -y as f64),
+                        winit::event::MouseScrollDelta::PixelDelta(position) => {
+                            (position.x, -position.y)
+                        }
+                    };
+                    Event::MouseWheel { delta_x, delta_y }
+                }
+                _ => Event::Undefined,
+            },
             _ => Event::Undefined,
         }
     }
 }
+
+/// Name of the key in the scheme of `Event::KeyboardInput`.
+#[cfg(not(target_os = "linux"))]
+fn key_name(key: winit::event::VirtualKeyCode) -> String {
+    use winit::event::VirtualKeyCode as Key;
+
+    let key = match key {
+        Key::Space => "Space",
+        Key::Return | Key::NumpadEnter => "Enter",
+        Key::Back => "Backspace",
+        Key::Up => "ArrowUp",
+        Key::Down => "ArrowDown",
+        Key::Left => "ArrowLeft",
+        Key::Right => "ArrowRight",
+        Key::LShift | Key::RShift => "Shift",
+        Key::LControl | Key::RControl => "Control",
+        Key::LAlt | Key::RAlt => "Alt",
+        Key::LWin | Key::RWin => "Meta",
+        Key::Capital => "CapsLock",
+        Key::Comma => ",",
+        Key::Period => ".",
+        Key::Slash => "/",
+        Key::Semicolon => ";",
+        Key::Apostrophe => "'",
+        Key::LBracket => "[",
+        Key::RBracket => "]",
+        Key::Backslash => "\\",
+        Key::Minus => "-",
+        Key::Equals => "=",
+        Key::Grave => "`",
+        key => {
+            let name = format!("{:?}", key);
+            // letters are `A`, digits `Key1` and `Numpad1`
+            let digit = name
+                .strip_prefix("Key")
+                .or_else(|| name.strip_prefix("Numpad"))
+                .filter(|digit| digit.len() == 1);
+            return match digit {
+                Some(digit) => digit.to_string(),
+                None if name.len() == 1 => name.to_lowercase(),
+                None => name,
+            };
+        }
+    };
+    key.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// The event of the request, as pushed to the backend script.
+    fn from_rpc(method: &str, params: Option<Value>) -> Option<Value> {
+        let request: RpcRequest = serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "method": method,
+            "params": params,
+        }))
+        .unwrap();
+        Event::from_rpc(&request).map(|event| serde_json::to_value(event).unwrap())
+    }
+
+    #[test]
+    fn maps_page_lifecycle() {
+        assert_eq!(
+            from_rpc("domContentLoaded", None),
+            Some(json!({ "event": "domContentLoaded" }))
+        );
+        assert_eq!(from_rpc("load", None), Some(json!({ "event": "load" })));
+        assert_eq!(
+            from_rpc("beforeUnload", Some(json!([]))),
+            Some(json!({ "event": "beforeUnload" }))
+        );
+    }
+
+    #[test]
+    fn maps_navigations() {
+        for method in &[
+            "navigationStarted",
+            "navigationFinished",
+            "navigationBlocked",
+        ] {
+            assert_eq!(
+                from_rpc(method, Some(json!([{ "url": "https://example.com/" }]))),
+                Some(json!({ "event": method, "url": "https://example.com/" }))
+            );
+        }
+    }
+
+    #[test]
+    fn maps_titles_and_console_messages() {
+        assert_eq!(
+            from_rpc("titleChanged", Some(json!([{ "title": "Home" }]))),
+            Some(json!({ "event": "titleChanged", "title": "Home" }))
+        );
+        assert_eq!(
+            from_rpc(
+                "consoleMessage",
+                Some(json!([{ "level": "warn", "message": "deprecated" }]))
+            ),
+            Some(json!({ "event": "consoleMessage", "level": "warn", "message": "deprecated" }))
+        );
+    }
+
+    #[test]
+    fn ignores_requests_missing_parameters() {
+        assert_eq!(from_rpc("navigationStarted", None), None);
+        assert_eq!(from_rpc("navigationStarted", Some(json!([]))), None);
+        assert_eq!(from_rpc("navigationStarted", Some(json!([{}]))), None);
+        assert_eq!(
+            from_rpc("navigationStarted", Some(json!({ "url": "a" }))),
+            None
+        );
+        assert_eq!(
+            from_rpc("titleChanged", Some(json!([{ "title": 42 }]))),
+            None
+        );
+        assert_eq!(
+            from_rpc("titleChanged", Some(json!([{ "title": null }]))),
+            None
+        );
+        // both are needed
+        assert_eq!(
+            from_rpc("consoleMessage", Some(json!([{ "level": "log" }]))),
+            None
+        );
+        assert_eq!(
+            from_rpc("consoleMessage", Some(json!([{ "message": "hi" }]))),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_the_first_parameter_only() {
+        assert_eq!(
+            from_rpc("titleChanged", Some(json!([{}, { "title": "Home" }]))),
+            None
+        );
+        assert_eq!(
+            from_rpc(
+                "titleChanged",
+                Some(json!([{ "title": "Home" }, { "title": "Other" }]))
+            ),
+            Some(json!({ "event": "titleChanged", "title": "Home" }))
+        );
+    }
+
+    #[test]
+    fn ignores_unknown_methods() {
+        assert_eq!(from_rpc("close", None), None);
+        assert_eq!(from_rpc("DomContentLoaded", None), None);
+        assert_eq!(from_rpc("", Some(json!([{ "url": "a" }]))), None);
+    }
+}
@@ -1,8 +1,192 @@
 use deno_core::serde::Serialize;
 
+#[cfg(target_os = "linux")]
+use crate::event::{ElementState, Event, Theme};
+#[cfg(target_os = "linux")]
+use gtk::prelude::*;
+use wry::webview::WebView;
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase", tag = "event")]
 pub enum WebViewStatus {
     Initialized,
     WindowCreated,
 }
+
+/// Lowercase hex encoding of the bytes.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Show or hide the webview window, hidden windows keep running.
+#[cfg(target_os = "linux")]
+pub fn set_visible(webview: &WebView, visible: bool) {
+    let window = webview.window();
+    if visible {
+        window.show();
+        window.present();
+    } else {
+        window.hide();
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_visible(webview: &WebView, visible: bool) {
+    webview.window().set_visible(visible);
+}
+
+/// Destroy the webview window.
+#[cfg(target_os = "linux")]
+pub fn destroy(webview: WebView) {
+    // wry doesn't destroy the gtk window when dropped
+    unsafe { webview.window().destroy() };
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn destroy(webview: WebView) {
+    drop(webview);
+}
+
+/// Evaluate javascript in the page.
+#[cfg(not(target_os = "linux"))]
+pub fn eval_script(webview: &mut WebView, js: &str) -> crate::Result<()> {
+    webview.dispatch_script(js)?;
+    webview.evaluate_script()?;
+    Ok(())
+}
+
+/// Decode an image in any format gdk-pixbuf knows.
+#[cfg(target_os = "linux")]
+pub fn pixbuf_from_bytes(bytes: &[u8]) -> crate::Result<gdk_pixbuf::Pixbuf> {
+    use gdk_pixbuf::PixbufLoaderExt;
+
+    let loader = gdk_pixbuf::PixbufLoader::new();
+    loader.write(bytes)?;
+    loader.close()?;
+    loader
+        .get_pixbuf()
+        .ok_or_else(|| anyhow::anyhow!("Unable to decode image"))
+}
+
+/// Find the webkit view wry attached to our window.
+#[cfg(target_os = "linux")]
+pub fn webkit_view(webview: &WebView) -> Option<webkit2gtk::WebView> {
+    find_webkit_view(webview.window().upcast_ref::<gtk::Widget>())
+}
+
+#[cfg(target_os = "linux")]
+fn find_webkit_view(widget: &gtk::Widget) -> Option<webkit2gtk::WebView> {
+    if let Ok(view) = widget.clone().downcast::<webkit2gtk::WebView>() {
+        return Some(view);
+    }
+
+    widget
+        .clone()
+        .downcast::<gtk::Container>()
+        .ok()?
+        .get_children()
+        .iter()
+        .find_map(find_webkit_view)
+}
+
+#[cfg(target_os = "linux")]
+pub fn current_theme(settings: &gtk::Settings) -> Theme {
+    let theme_name = settings
+        .get_property_gtk_theme_name()
+        .map(|name| name.to_lowercase())
+        .unwrap_or_default();
+
+    if settings.get_property_gtk_application_prefer_dark_theme() || theme_name.ends_with("-dark") {
+        Theme::Dark
+    } else {
+        Theme::Light
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn keyboard_event(event: &gdk::EventKey, state: ElementState) -> Event {
+    Event::KeyboardInput {
+        key: key_name(event),
+        scancode: event.get_hardware_keycode() as u32,
+        state,
+        modifiers: event.get_state().into(),
+    }
+}
+
+/// Keyval of the base level of the pressed key, the keyval of the event
+/// has the modifiers applied (`A`, `exclam`).
+#[cfg(target_os = "linux")]
+pub fn base_keyval(event: &gdk::EventKey) -> u32 {
+    gdk::Keymap::get_default()
+        .and_then(|keymap| {
+            keymap.translate_keyboard_state(
+                event.get_hardware_keycode() as u32,
+                gdk::ModifierType::empty(),
+                event.get_group() as i32,
+            )
+        })
+        .map(|(keyval, ..)| keyval)
+        .unwrap_or_else(|| *event.get_keyval())
+}
+
+/// Name of the key in the scheme of `Event::KeyboardInput`.
+#[cfg(target_os = "linux")]
+fn key_name(event: &gdk::EventKey) -> Option<String> {
+    let keyval = base_keyval(event);
+    let name = gdk::keyval_name(keyval)?;
+    let key = match name.as_str() {
+        "space" => "Space",
+        "Return" | "KP_Enter" => "Enter",
+        "BackSpace" => "Backspace",
+        "ISO_Left_Tab" => "Tab",
+        "Page_Up" => "PageUp",
+        "Page_Down" => "PageDown",
+        "Up" => "ArrowUp",
+        "Down" => "ArrowDown",
+        "Left" => "ArrowLeft",
+        "Right" => "ArrowRight",
+        "Shift_L" | "Shift_R" => "Shift",
+        "Control_L" | "Control_R" => "Control",
+        "Alt_L" | "Alt_R" => "Alt",
+        "Super_L" | "Super_R" | "Meta_L" | "Meta_R" => "Meta",
+        "Caps_Lock" => "CapsLock",
+        _ => {
+            return match gdk::keyval_to_unicode(keyval) {
+                Some(c) if !c.is_control() => Some(c.to_lowercase().to_string()),
+                _ => Some(name.to_string()),
+            }
+        }
+    };
+    Some(key.to_string())
+}
+
+#[cfg(target_os = "linux")]
+pub fn mouse_event(event: &gdk::EventButton, state: ElementState) -> Event {
+    Event::MouseInput {
+        button: event.get_button().into(),
+        state,
+        modifiers: event.get_state().into(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn wheel_event(event: &gdk::EventScroll) -> Event {
+    let (delta_x, delta_y) = match event.get_direction() {
+        gdk::ScrollDirection::Up => (0.0, -1.0),
+        gdk::ScrollDirection::Down => (0.0, 1.0),
+        gdk::ScrollDirection::Left => (-1.0, 0.0),
+        gdk::ScrollDirection::Right => (1.0, 0.0),
+        _ => event.get_delta(),
+    };
+    Event::MouseWheel { delta_x, delta_y }
+}
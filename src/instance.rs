@@ -0,0 +1,158 @@
+use crate::event::Event;
+
+#[cfg(target_os = "linux")]
+use anyhow::bail;
+#[cfg(target_os = "linux")]
+use gio::prelude::*;
+#[cfg(target_os = "linux")]
+use gio::{ApplicationFlags, Cancellable};
+
+/// Identity of the app, from the metadata of a compiled binary or the
+/// `wry run` flags.
+#[derive(Debug, Clone, Default)]
+pub struct AppOptions {
+    /// reverse DNS name like `com.example.App`
+    pub app_id: Option<String>,
+    /// forward later launches to the running instance
+    pub single_instance: bool,
+    /// URL schemes opening the app, delivered with `openUrl` events
+    pub schemes: Vec<String>,
+}
+
+/// Arguments which are URLs of our schemes.
+fn urls(args: &[String], schemes: &[String]) -> Vec<String> {
+    args.iter()
+        .filter(|arg| {
+            arg.find(':').map_or(false, |end| {
+                schemes
+                    .iter()
+                    .any(|scheme| scheme.eq_ignore_ascii_case(&arg[..end]))
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// `openUrl` events of a launch, kept for the first webview when there is
+/// none yet.
+fn open_urls(args: &[String], schemes: &[String]) {
+    for url in urls(args.get(1..).unwrap_or_default(), schemes) {
+        crate::broadcast_event(Event::OpenUrl { url });
+    }
+}
+
+/// Register the app on the session bus and send the URLs it was launched
+/// with.
+///
+/// Returns false when another instance is running and got our arguments,
+/// this process should exit right away.
+#[cfg(target_os = "linux")]
+pub fn start(options: &AppOptions) -> crate::Result<bool> {
+    // the first one is the executable
+    let args: Vec<String> = std::env::args_os()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    open_urls(&args, &options.schemes);
+
+    let app_id = match &options.app_id {
+        Some(app_id) => app_id,
+        None if options.single_instance => bail!("Single instance mode needs an app id"),
+        None => return Ok(true),
+    };
+    if !gio::Application::id_is_valid(app_id) {
+        bail!(
+            "Invalid app id {}, use a reverse DNS name like com.example.App",
+            app_id
+        );
+    }
+
+    // gio apps with an id are unique unless told otherwise
+    let flags = if options.single_instance {
+        ApplicationFlags::HANDLES_COMMAND_LINE
+    } else {
+        ApplicationFlags::NON_UNIQUE
+    };
+    let app = gtk::Application::new(Some(app_id), flags)?;
+    app.register(None::<&Cancellable>)?;
+
+    if app.get_is_remote() {
+        // the running instance receives them with the `command-line` signal
+        app.run(&args);
+        return Ok(false);
+    }
+
+    let schemes = options.schemes.clone();
+    app.connect_command_line(move |_app, command_line| {
+        let args: Vec<String> = command_line
+            .get_arguments()
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        open_urls(&args, &schemes);
+        crate::broadcast_event(Event::SecondInstance {
+            args,
+            cwd: command_line.get_cwd(),
+        });
+        0
+    });
+
+    crate::GTK_APPLICATION.with(|cell| cell.replace(app));
+    Ok(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn start(options: &AppOptions) -> crate::Result<bool> {
+    let args: Vec<String> = std::env::args_os()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    open_urls(&args, &options.schemes);
+
+    if options.single_instance {
+        eprintln!("Single instance mode is not supported on this platform yet");
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_urls_of_our_schemes() {
+        let args = strings(&["myapp://open?id=1", "other://x", "--flag", "myapp:plain"]);
+        assert_eq!(
+            urls(&args, &strings(&["myapp"])),
+            strings(&["myapp://open?id=1", "myapp:plain"])
+        );
+    }
+
+    #[test]
+    fn matches_schemes_ignoring_case() {
+        let args = strings(&["MyApp://open", "second://x"]);
+        assert_eq!(
+            urls(&args, &strings(&["myapp", "second"])),
+            strings(&["MyApp://open", "second://x"])
+        );
+    }
+
+    #[test]
+    fn ignores_arguments_without_scheme() {
+        let args = strings(&["file.txt", "./myapp", "", ":myapp"]);
+        assert!(urls(&args, &strings(&["myapp"])).is_empty());
+    }
+
+    #[test]
+    fn needs_the_whole_scheme() {
+        let args = strings(&["myapps://open", "my://open"]);
+        assert!(urls(&args, &strings(&["myapp"])).is_empty());
+    }
+
+    #[test]
+    fn no_schemes_no_urls() {
+        assert!(urls(&strings(&["myapp://open"]), &[]).is_empty());
+    }
+}
@@ -2,11 +2,11 @@ pub use anyhow::Result;
 use clap::{App, Arg};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{cell::RefCell, collections::HashMap, io::prelude::*, path::PathBuf};
+use std::{cell::RefCell, collections::HashMap, path::Path};
 
 use deno_core::error::AnyError;
-use deno_core::json_op_sync;
 use deno_core::FsModuleLoader;
+use deno_core::{bin_op_sync, json_op_sync};
 use deno_core::{resolve_path, resolve_url};
 use deno_runtime::permissions::Permissions;
 use deno_runtime::worker::MainWorker;
@@ -14,8 +14,8 @@ use deno_runtime::worker::WorkerOptions;
 use std::rc::Rc;
 use std::sync::Arc;
 
-use deno_core::error::anyhow;
-use wry::webview::{RpcRequest, WebView, WebViewBuilder};
+use deno_core::error::{anyhow, custom_error, type_error};
+use wry::webview::{FileDropEvent, RpcRequest, WebView, WebViewBuilder};
 
 #[cfg(not(target_os = "linux"))]
 use winit::{
@@ -24,21 +24,49 @@ use winit::{
     window::Window,
 };
 
+#[cfg(target_os = "linux")]
+mod accelerator;
+mod asset_cache;
+mod assets;
+mod clipboard;
+mod dialog;
 mod embed_assets;
 mod event;
 mod helpers;
+mod instance;
+mod menu;
+mod navigation;
+mod notification;
+mod protocol;
+mod shortcut;
+mod signing;
 mod standalone;
+mod tray;
 
 use serde_json::json;
 
-use embed_assets::{Assets, EmbeddedAssets};
+use asset_cache::AssetCache;
+use assets::{Assets, DirAssets, OverlayAssets, ZipAssets};
+use dialog::{FileDialogKind, FileDialogOptions, MessageOptions};
+use embed_assets::{CompressionOptions, EmbeddedAssets, DEFAULT_COMPRESSION_LEVEL};
 use event::Event;
 use helpers::WebViewStatus;
+use instance::AppOptions;
+use menu::MenuItem;
+use navigation::NavigationPolicy;
+use notification::NotificationOptions;
+use protocol::{AssetOptions, Body, Request, Response};
+use signing::SigningKey;
+use tray::TrayOptions;
 
 #[cfg(target_os = "linux")]
 use gio::{ApplicationExt as GioApplicationExt, Cancellable};
 #[cfg(target_os = "linux")]
-use gtk::{Application as GtkApp, ApplicationWindow, GtkWindowExt, Inhibit, WidgetExt};
+use gtk::{
+    Application as GtkApp, ApplicationWindow, GtkWindowExt, Inhibit, SettingsExt, WidgetExt,
+};
+#[cfg(target_os = "linux")]
+use webkit2gtk::{LoadEvent, WebViewExt};
 
 thread_local! {
   static INDEX: RefCell<u64> = RefCell::new(0);
@@ -49,6 +77,14 @@ thread_local! {
   static WEBVIEW_MAP: RefCell<HashMap<u64, WebView>> = RefCell::new(HashMap::new());
   static WEBVIEW_STATUS: RefCell<HashMap<u64, WebViewStatus>> = RefCell::new(HashMap::new());
   static STACK_MAP: RefCell<HashMap<u64, Vec<event::Event>>> = RefCell::new(HashMap::new());
+  // broadcast before the first webview was created
+  static PENDING_EVENTS: RefCell<Vec<event::Event>> = RefCell::new(Vec::new());
+  #[cfg(not(target_os = "linux"))]
+  static MINIMIZED: RefCell<std::collections::HashSet<u64>> = RefCell::new(Default::default());
+  // webviews hidden instead of closed, usually to keep living in the tray
+  static HIDE_ON_CLOSE: RefCell<std::collections::HashSet<u64>> = RefCell::new(Default::default());
+  // webviews whose backend answers closeRequested, the others close right away
+  static INTERCEPT_CLOSE: RefCell<std::collections::HashSet<u64>> = RefCell::new(Default::default());
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -61,6 +97,82 @@ struct ResourceId {
     rid: u32,
 }
 
+// push an event onto the stack of the given webview, to be pulled with wry_step,
+// events of unknown webviews are dropped
+fn push_event(id: u64, event: Event) {
+    STACK_MAP.with(|cell| {
+        if let Some(stack) = cell.borrow_mut().get_mut(&id) {
+            stack.push(event);
+        }
+    });
+}
+
+// push the event to every open webview, or keep it for the first one
+fn broadcast_event(event: Event) {
+    let ids: Vec<u64> = WEBVIEW_MAP.with(|cell| cell.borrow().keys().copied().collect());
+    if ids.is_empty() {
+        PENDING_EVENTS.with(|cell| cell.borrow_mut().push(event));
+    } else {
+        for id in ids {
+            push_event(id, event.clone());
+        }
+    }
+}
+
+fn hide_on_close(id: u64) -> bool {
+    HIDE_ON_CLOSE.with(|cell| cell.borrow().contains(&id))
+}
+
+fn intercepts_close(id: u64) -> bool {
+    INTERCEPT_CLOSE.with(|cell| cell.borrow().contains(&id))
+}
+
+// close the window for good, or only hide it with `hideOnClose`, returns
+// whether the window is gone
+fn close_webview(id: u64) -> Result<bool> {
+    let hide = hide_on_close(id);
+    if hide {
+        with_webview(id, |webview| {
+            helpers::set_visible(webview, false);
+            Ok(())
+        })?;
+    } else {
+        let webview = WEBVIEW_MAP
+            .with(|cell| cell.borrow_mut().remove(&id))
+            .ok_or_else(|| anyhow!("Could not find webview with id: {}", id))?;
+        INTERCEPT_CLOSE.with(|cell| cell.borrow_mut().remove(&id));
+        tray::remove_tray(id);
+        shortcut::unregister_all(id);
+        helpers::destroy(webview);
+    }
+    push_event(id, Event::Close);
+    Ok(!hide)
+}
+
+// run the closure with the webview matching the given id
+fn with_webview<T>(id: u64, f: impl FnOnce(&mut WebView) -> Result<T>) -> Result<T> {
+    WEBVIEW_MAP.with(|cell| {
+        let mut webviews = cell.borrow_mut();
+        let webview = webviews
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("Could not find webview with id: {}", id))?;
+        f(webview)
+    })
+}
+
+// required arguments of our ops, a missing one throws a TypeError
+fn str_arg<'a>(json: &'a Value, name: &str) -> Result<&'a str> {
+    json[name]
+        .as_str()
+        .ok_or_else(|| type_error(format!("Expected a string for {}", name)))
+}
+
+fn u64_arg(json: &Value, name: &str) -> Result<u64> {
+    json[name]
+        .as_u64()
+        .ok_or_else(|| type_error(format!("Expected an integer for {}", name)))
+}
+
 fn get_error_class_name(e: &AnyError) -> &'static str {
     deno_runtime::errors::get_error_class_name(e).unwrap_or("Error")
 }
@@ -68,33 +180,169 @@ fn get_error_class_name(e: &AnyError) -> &'static str {
 #[tokio::main]
 async fn main() -> Result<()> {
     let standalone_res = match standalone::extract_standalone() {
-        Ok(Some((metadata, assets))) => standalone::run(assets, metadata).await,
-        Ok(None) => Ok(()),
+        Ok(Some((metadata, assets))) => standalone::run(assets, metadata).await.map(|_| true),
+        Ok(None) => Ok(false),
         Err(err) => Err(err),
     };
 
-    if let Err(_err) = standalone_res {
-        std::process::exit(1);
+    match standalone_res {
+        // the arguments of a compiled app are its own
+        Ok(true) => return Ok(()),
+        Ok(false) => {}
+        Err(err) => {
+            eprintln!("{:#}", err);
+            std::process::exit(1);
+        }
     }
 
     let matches = App::new("wry")
         .subcommand(
             App::new("run")
                 .about("Run application")
-                .arg(Arg::with_name("js-file").required(true)),
+                .arg(
+                    Arg::with_name("js-file")
+                        .required(true)
+                        .help("Entry point, or a zip archive of the app with an index.js"),
+                )
+                .arg(
+                    Arg::with_name("overlay")
+                        .long("overlay")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .help("Directory whose files replace the app assets"),
+                )
+                .arg(
+                    Arg::with_name("app-id")
+                        .long("app-id")
+                        .takes_value(true)
+                        .value_name("ID")
+                        .help("Reverse DNS name of the app, like com.example.App"),
+                )
+                .arg(
+                    Arg::with_name("single-instance")
+                        .long("single-instance")
+                        .requires("app-id")
+                        .help("Forward later launches to the running instance"),
+                ),
         )
         .subcommand(
             App::new("compile")
                 .about("Compile application binary")
-                .arg(Arg::with_name("js-file").required(true)),
+                .arg(Arg::with_name("js-file").required(true))
+                .arg(
+                    Arg::with_name("compression-level")
+                        .long("compression-level")
+                        .takes_value(true)
+                        .help("zstd compression level of the assets, from 1 to 22"),
+                )
+                .arg(
+                    Arg::with_name("no-compression")
+                        .long("no-compression")
+                        .help("Store all assets uncompressed"),
+                )
+                .arg(
+                    Arg::with_name("store-raw")
+                        .long("store-raw")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Store assets with this extension uncompressed"),
+                )
+                .arg(
+                    Arg::with_name("cache-dir")
+                        .long("cache-dir")
+                        .takes_value(true)
+                        .default_value(".wry-cache")
+                        .help("Keep compressed assets in this directory between builds"),
+                )
+                .arg(
+                    Arg::with_name("no-cache")
+                        .long("no-cache")
+                        .help("Compress all assets again, without reading or writing the cache"),
+                )
+                .arg(
+                    Arg::with_name("encrypt")
+                        .long("encrypt")
+                        .help("Encrypt the embedded assets, this is obfuscation not protection"),
+                )
+                .arg(
+                    Arg::with_name("overlay")
+                        .long("overlay")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .help("Directory, relative to the binary, whose files replace the embedded assets"),
+                )
+                .arg(
+                    Arg::with_name("sign")
+                        .long("sign")
+                        .takes_value(true)
+                        .value_name("KEY_FILE")
+                        .help("Sign the embedded bundle with a key generated by `wry keygen`"),
+                )
+                .arg(
+                    Arg::with_name("app-id")
+                        .long("app-id")
+                        .takes_value(true)
+                        .value_name("ID")
+                        .help("Reverse DNS name of the app, like com.example.App"),
+                )
+                .arg(
+                    Arg::with_name("single-instance")
+                        .long("single-instance")
+                        .requires("app-id")
+                        .help("Forward later launches to the running instance"),
+                )
+                .arg(
+                    Arg::with_name("scheme")
+                        .long("scheme")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("URL scheme opening the app, registered for the user when the binary runs on Linux"),
+                ),
+        )
+        .subcommand(
+            App::new("keygen")
+                .about("Generate a key to sign compiled binaries")
+                .arg(Arg::with_name("key-file").required(true)),
         )
         .get_matches();
 
     if let Some(run_matches) = matches.subcommand_matches("run") {
         // we should have a path like
         // ./examples/project1/src/index.html
-        let entry_point = run_matches.value_of("js-file").unwrap();
-        run_wry(entry_point, None).await?
+        let entry_point = Path::new(run_matches.value_of("js-file").unwrap());
+        let is_zip = entry_point
+            .extension()
+            .map_or(false, |extension| extension == "zip");
+        let (main_module, mut assets): (String, Box<dyn Assets>) = if is_zip {
+            ("index.js".into(), Box::new(ZipAssets::open(entry_point)?))
+        } else {
+            let root = entry_point
+                .parent()
+                .filter(|root| !root.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."));
+            (
+                entry_point
+                    .file_name()
+                    .ok_or_else(|| anyhow!("Invalid entry point"))?
+                    .to_string_lossy()
+                    .to_string(),
+                Box::new(DirAssets::new(root)?),
+            )
+        };
+        if let Some(overlay) = run_matches.value_of("overlay") {
+            assets = Box::new(
+                OverlayAssets::new(Box::new(DirAssets::new(Path::new(overlay))?), assets)
+                    .with_protected(&main_module),
+            );
+        }
+        let app = AppOptions {
+            app_id: run_matches.value_of("app-id").map(String::from),
+            single_instance: run_matches.is_present("single-instance"),
+            schemes: Vec::new(),
+        };
+        run_wry(&main_module, Rc::from(assets), &app).await?
     } else if let Some(build_matches) = matches.subcommand_matches("compile") {
         // we should have a path like
         // ./examples/project1/src/index.html
@@ -103,69 +351,136 @@ async fn main() -> Result<()> {
         // ./examples/project1/src/
         let root_path = std::fs::canonicalize(root_entry_point.parent().unwrap())?;
 
+        let compression = CompressionOptions {
+            level: build_matches
+                .value_of("compression-level")
+                .map(|level| level.parse())
+                .transpose()
+                .map_err(|_| anyhow!("Invalid compression level"))?
+                .unwrap_or(DEFAULT_COMPRESSION_LEVEL),
+            disabled: build_matches.is_present("no-compression"),
+            raw_extensions: build_matches
+                .values_of("store-raw")
+                .map(|values| values.map(String::from).collect())
+                .unwrap_or_default(),
+        };
+        if !(1..=22).contains(&compression.level) {
+            return Err(anyhow!("Compression level must be between 1 and 22"));
+        }
+
+        let cache = if build_matches.is_present("no-cache") {
+            None
+        } else {
+            Some(AssetCache::open(Path::new(
+                build_matches.value_of("cache-dir").unwrap(),
+            ))?)
+        };
+
         // embed all assets
-        let assets = EmbeddedAssets::new(&root_path)?;
+        let mut assets = EmbeddedAssets::new(&root_path, &compression, cache.as_ref())?;
+        if build_matches.is_present("encrypt") {
+            assets.encrypt()?;
+        }
+        assets.report();
+
+        let signing_key = build_matches
+            .value_of("sign")
+            .map(|path| SigningKey::open(Path::new(path)))
+            .transpose()?;
 
-        standalone::compile_command(&assets, None, None)?;
+        let metadata = standalone::Metadata {
+            overlay: build_matches.value_of("overlay").map(String::from),
+            app_id: build_matches.value_of("app-id").map(String::from),
+            single_instance: build_matches.is_present("single-instance"),
+            schemes: build_matches
+                .values_of("scheme")
+                .map(|values| values.map(String::from).collect())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        standalone::compile_command(&assets, metadata, None, None, signing_key.as_ref())?;
+    } else if let Some(keygen_matches) = matches.subcommand_matches("keygen") {
+        let key_file = Path::new(keygen_matches.value_of("key-file").unwrap());
+        let public_key = signing::generate_key(key_file)?;
+        println!("Signing key written to {}", key_file.display());
+        println!("Public key: {}", public_key);
     }
 
     Ok(())
 }
 
-pub async fn run_wry(main_module_path: &str, assets: Option<EmbeddedAssets>) -> Result<()> {
+pub async fn run_wry(
+    main_module_path: &str,
+    assets: Rc<dyn Assets>,
+    app: &AppOptions,
+) -> Result<()> {
+    if !instance::start(app)? {
+        return Ok(());
+    }
+
     let module_loader: Rc<dyn deno_core::ModuleLoader>;
-    let main_module_pathbuf = PathBuf::from(main_module_path);
     let main_module: deno_core::ModuleSpecifier;
+    let root_file_name = Path::new(main_module_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
 
-    // our project path source should be
-    // ./examples/project1/src/
-    let mut root_path = None;
-
-    if let Some(assets) = assets.clone() {
-        println!("GET SOURCE");
+    if let Some(root) = assets.root() {
+        // modules are loaded from the disk, imports work as usual
+        module_loader = Rc::new(FsModuleLoader);
+        main_module = resolve_path(&root.join(main_module_path).to_string_lossy())?;
+    } else {
         let source_code = assets
             .get(main_module_path)
-            .expect("Unable to extract source code");
-        println!("SOURCE {}", String::from_utf8(source_code.clone())?);
+            .ok_or_else(|| anyhow!("Unable to extract source code of {}", main_module_path))?;
         module_loader = Rc::new(standalone::EmbeddedModuleLoader(String::from_utf8(
             source_code,
         )?));
         main_module = resolve_url(standalone::SPECIFIER)?;
-    } else {
-        root_path = Some(std::fs::canonicalize(
-            main_module_pathbuf.clone().parent().unwrap(),
-        )?);
-        module_loader = Rc::new(FsModuleLoader);
-        main_module = resolve_path(main_module_path)?;
     }
 
-    // load file from given path or from embed assets
+    // load file from our assets, whatever backs them
     fn file_resolver(
-        file: &str,
+        request: &Request,
         root_file_name: &str,
-        root: Option<std::path::PathBuf>,
-        assets: Option<EmbeddedAssets>,
-    ) -> Result<Vec<u8>> {
-        if let Some(assets) = assets {
-            let mut file_name = file;
-            let trimmed = file_name.replace("./", "");
-            if file_name.starts_with("./") {
-                file_name = &trimmed;
-            }
-            let trimed_file = file_name.replace(&format!("{}/", root_file_name), "");
-            Ok(assets.get(trimed_file.as_str()).unwrap())
+        assets: &dyn Assets,
+        options: &AssetOptions,
+    ) -> Result<Response> {
+        // reject anything trying to escape the asset root
+        let path = match protocol::asset_path(&request.uri, root_file_name) {
+            Some(path) => path,
+            None => return Ok(Response::forbidden()),
+        };
+
+        let path = match protocol::resolve_asset(&path, options, |path| assets.size(path).is_some())
+        {
+            Some(path) => path,
+            None => return Ok(Response::not_found(assets.get("404.html"))),
+        };
+        let size = assets.size(&path).unwrap_or_default();
+        let mime_type = assets
+            .mime_type(&path)
+            .unwrap_or_else(|| protocol::mime_type(&path).to_string());
+        let response = Response::ranged(request, &mime_type, size, |range| {
+            assets.read_range(&path, range)
+        })
+        .ok_or_else(|| anyhow!("Unable to read asset {}", path))?;
+
+        let cache_control = if assets.is_mutable() {
+            "no-cache"
         } else {
-            let mut trimed_file = file.replace(&format!("{}/", root_file_name), "");
-            if trimed_file.starts_with("./") {
-                trimed_file = trimed_file.replace("./", "");
-            }
-            let mut file = std::fs::File::open(std::fs::canonicalize(
-                root.expect("No root path found").join(trimed_file),
-            )?)?;
-            let mut buffer = Vec::new();
-            file.read_to_end(&mut buffer)?;
-            Ok(buffer)
-        }
+            // embedded assets can't change while the app is running
+            "public, max-age=3600"
+        };
+        Ok(response.with_header("Cache-Control", cache_control))
+    }
+
+    // backend access to the app assets
+    fn read_asset(path: &str, assets: &dyn Assets) -> Result<Vec<u8>> {
+        let not_found = || custom_error("NotFound", format!("Asset not found: {}", path));
+        let key = protocol::asset_path(path, "").ok_or_else(not_found)?;
+        assets.get(&key).ok_or_else(not_found)
     }
 
     // if we want to support web worker, we need to spawn js_runtime worker
@@ -200,11 +515,54 @@ pub async fn run_wry(main_module_path: &str, assets: Option<EmbeddedAssets>) ->
 
     let mut worker = MainWorker::from_options(main_module.clone(), permissions, &options);
 
+    let op_assets = assets.clone();
+    worker.js_runtime.register_op(
+        "wry_assets_read",
+        // the path comes as the first buffer, the content goes back as a Uint8Array
+        bin_op_sync(move |_state, _arg, zero_copy| {
+            let path = zero_copy
+                .first()
+                .ok_or_else(|| type_error("Missing asset path"))?;
+            let path = std::str::from_utf8(path).map_err(|_| type_error("Invalid asset path"))?;
+            read_asset(path, op_assets.as_ref())
+        }),
+    );
+
+    let op_assets = assets.clone();
+    worker.js_runtime.register_op(
+        "wry_assets_list",
+        json_op_sync(move |_state, _json: Value, _zero_copy| {
+            let mut keys = op_assets.keys();
+            keys.sort();
+            Ok(json!(keys))
+        }),
+    );
+
+    let op_assets = assets.clone();
+    worker.js_runtime.register_op(
+        "wry_set_tray",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let options: TrayOptions = serde_json::from_value(json.clone())?;
+            tray::set_tray(id, &options, op_assets.as_ref())?;
+            Ok(json!(null))
+        }),
+    );
+
+    let op_assets = assets.clone();
+    worker.js_runtime.register_op(
+        "wry_notify",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let options: NotificationOptions = serde_json::from_value(json)?;
+            Ok(json!(notification::notify(&options, op_assets.as_ref())?))
+        }),
+    );
+
     // return pending Events
     worker.js_runtime.register_op(
         "wry_step",
         json_op_sync(move |_state, json: Value, _zero_copy| {
-            let id = json["id"].as_u64().unwrap();
+            let id = u64_arg(&json, "id")?;
             STACK_MAP.with(|cell| {
                 let mut stack_map = cell.borrow_mut();
                 if let Some(stack) = stack_map.get_mut(&id) {
@@ -222,122 +580,151 @@ pub async fn run_wry(main_module_path: &str, assets: Option<EmbeddedAssets>) ->
     // this loop record event from winit and if needed inject them inside our STACK_MAP
     // to be pulled with wry_step -- we also dispatch event to our webview if we got a resize
     // or if we got a close event
-    worker.js_runtime.register_op("wry_loop", json_op_sync(move |_state, json: Value, _zero_copy| {
-         let id = json["id"].as_u64().unwrap();
-         let mut should_stop_loop = false;
-
-         #[cfg(target_os = "linux")]
-         {
-            should_stop_loop = gtk::main_iteration_do(false) == false;
-            // set this webview as WindowCreated if needed
-            WEBVIEW_MAP.with(|cell| {
-                let webview_map = cell.borrow();
-                if let Some(webview) = webview_map.get(&id) {
-                    WEBVIEW_STATUS.with(|cell| {
-                        let mut status_map = cell.borrow_mut();
-                        if let Some(status) = status_map.get_mut(&id) {
-                            match status {
-                                &mut WebViewStatus::Initialized => {
-                                    *status = WebViewStatus::WindowCreated;
-                                    STACK_MAP.with(|cell| {
-
-                            let mut stack_map = cell.borrow_mut();
-                            if let Some(stack) = stack_map.get_mut(&id) {
-                                stack.push(Event::WindowCreated);
-                            } else {
-                                panic!("Could not find stack with id {} to push onto stack", id);
+    worker.js_runtime.register_op(
+        "wry_loop",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let mut should_stop_loop = false;
+
+            #[cfg(target_os = "linux")]
+            {
+                should_stop_loop = gtk::main_iteration_do(false) == false;
+                shortcut::poll_global();
+                // set this webview as WindowCreated if needed
+                WEBVIEW_MAP.with(|cell| {
+                    let webview_map = cell.borrow();
+                    if let Some(webview) = webview_map.get(&id) {
+                        WEBVIEW_STATUS.with(|cell| {
+                            let mut status_map = cell.borrow_mut();
+                            if let Some(status) = status_map.get_mut(&id) {
+                                match status {
+                                    &mut WebViewStatus::Initialized => {
+                                        *status = WebViewStatus::WindowCreated;
+                                        push_event(id, Event::WindowCreated);
+                                    }
+                                    _ => {}
+                                };
                             }
                         });
+                    };
+                });
+            }
+
+            #[cfg(not(target_os = "linux"))]
+            EVENT_LOOP.with(|cell| {
+                let event_loop = &mut *cell.borrow_mut();
+                event_loop.run_return(|event, _, control_flow| {
+                    *control_flow = ControlFlow::Exit;
+
+                    // window events belong to the webview of the window, the
+                    // loop is shared by all of them
+                    let target = match &event {
+                        winit::event::Event::WindowEvent { window_id, .. } => {
+                            WEBVIEW_MAP.with(|cell| {
+                                cell.borrow()
+                                    .iter()
+                                    .find(|(_, webview)| webview.window().id() == *window_id)
+                                    .map(|(id, _)| *id)
+                            })
+                        }
+                        _ => Some(id),
+                    };
+                    let target = match target {
+                        Some(target) => target,
+                        // the window is already closed
+                        None => return,
+                    };
+
+                    WEBVIEW_MAP.with(|cell| {
+                        let webview_map = cell.borrow();
+
+                        if let Some(webview) = webview_map.get(&target) {
+                            match event {
+                                winit::event::Event::WindowEvent {
+                                    event: winit::event::WindowEvent::Resized(_),
+                                    ..
+                                } => {
+                                    webview.resize().unwrap();
+                                }
+                                winit::event::Event::MainEventsCleared => {
+                                    webview.window().request_redraw();
                                 }
-                                _ => {}
+                                winit::event::Event::RedrawRequested(_) => {}
+                                _ => (),
                             };
+
+                            // set this webview as WindowCreated if needed
+                            WEBVIEW_STATUS.with(|cell| {
+                                let mut status_map = cell.borrow_mut();
+                                if let Some(status) = status_map.get_mut(&target) {
+                                    match status {
+                                        &mut WebViewStatus::Initialized => {
+                                            *status = WebViewStatus::WindowCreated;
+                                            push_event(target, Event::WindowCreated);
+                                        }
+                                        _ => {}
+                                    };
+                                }
+                            });
                         }
                     });
-                };
+
+                    // add our event inside our stack to be pulled by the next step
+                    match Event::from(event) {
+                        Event::Undefined => {}
+                        Event::Minimized => {
+                            MINIMIZED.with(|cell| cell.borrow_mut().insert(target));
+                            push_event(target, Event::Minimized);
+                        }
+                        wry_event @ Event::Resized { .. } => {
+                            // winit has no restore event, the first non-zero resize
+                            // after a minimize is our restore
+                            if MINIMIZED.with(|cell| cell.borrow_mut().remove(&target)) {
+                                push_event(target, Event::Restored);
+                            }
+                            push_event(target, wry_event);
+                        }
+                        Event::CloseRequested if !intercepts_close(target) => {
+                            if let Err(err) = close_webview(target) {
+                                eprintln!("{:#}", err);
+                            }
+                        }
+                        wry_event => push_event(target, wry_event),
+                    };
+                });
             });
-         }
-
-         #[cfg(not(target_os = "linux"))]
-         EVENT_LOOP.with(|cell| {
-             let event_loop = &mut *cell.borrow_mut();
-             event_loop.run_return(|event, _, control_flow| {
-                 *control_flow = ControlFlow::Exit;
-
-                 WEBVIEW_MAP.with(|cell| {
-                     let webview_map = cell.borrow();
-
-                     if let Some(webview) = webview_map.get(&id) {
-                         match event {
-                             winit::event::Event::WindowEvent {
-                                 event: winit::event::WindowEvent::CloseRequested,
-                                 ..
-                             } => {
-                                 should_stop_loop = true;
-                             }
-                             winit::event::Event::WindowEvent {
-                                 event: winit::event::WindowEvent::Resized(_),
-                                 ..
-                             } => {
-                                 webview.resize().unwrap();
-                             }
-                             winit::event::Event::MainEventsCleared => {
-                                 webview.window().request_redraw();
-                             }
-                             winit::event::Event::RedrawRequested(_) => {}
-                             _ => (),
-                         };
-
-                         // set this webview as WindowCreated if needed
-                         WEBVIEW_STATUS.with(|cell| {
-                             let mut status_map = cell.borrow_mut();
-                             if let Some(status) = status_map.get_mut(&id) {
-                                 match status {
-                                     &mut WebViewStatus::Initialized => {
-                                         *status = WebViewStatus::WindowCreated;
-                                         STACK_MAP.with(|cell| {
-
-                                   let mut stack_map = cell.borrow_mut();
-                                   if let Some(stack) = stack_map.get_mut(&id) {
-                                       stack.push(Event::WindowCreated);
-                                   } else {
-                                       panic!("Could not find stack with id {} to push onto stack", id);
-                                   }
-                               });
-                                     }
-                                     _ => {}
-                                 };
-                             }
-                         });
-                     }
-                 });
-
-                 // add our event inside our stack to be pulled by the next step
-                 STACK_MAP.with(|cell| {
-                     let mut stack_map = cell.borrow_mut();
-                     if let Some(stack) = stack_map.get_mut(&id) {
-                         let wry_event = Event::from(event);
-                         match wry_event {
-                             Event::Undefined => {}
-                             _ => {
-                                 stack.push(wry_event);
-                             }
-                         };
-                     } else {
-                         panic!("Could not find stack with id {} to push onto stack", id);
-                     }
-                 });
-             });
-         });
-
-         Ok(json!(should_stop_loop))
-       }));
+
+            // the window was closed with `wry_close`
+            if !WEBVIEW_MAP.with(|cell| cell.borrow().contains_key(&id)) {
+                should_stop_loop = true;
+            }
+
+            Ok(json!(should_stop_loop))
+        }),
+    );
 
     worker.js_runtime.register_op(
         "wry_new",
         json_op_sync(move |_state, json: Value, _zero_copy| {
-            let url = json["url"].as_str().unwrap();
-            let root_entry_point = main_module_pathbuf.clone();
-            let root_path = root_path.clone();
+            let url = str_arg(&json, "url")?;
+            let policy: NavigationPolicy = serde_json::from_value(json.clone())?;
+            let asset_options: AssetOptions = serde_json::from_value(json.clone())?;
+            // schemes answered by the backend script
+            let protocols: Vec<String> =
+                serde_json::from_value(json["protocols"].clone()).unwrap_or_default();
+            if let Some(scheme) = protocols
+                .iter()
+                .find(|scheme| protocol::is_reserved(scheme))
+            {
+                return Err(anyhow!("Protocol {} is reserved", scheme));
+            }
+            #[cfg(not(target_os = "linux"))]
+            if !protocols.is_empty() {
+                return Err(anyhow!(
+                    "Custom protocols are not supported on this platform yet"
+                ));
+            }
+            let root_file_name = root_file_name.clone();
             let assets = assets.clone();
 
             println!("{}", url);
@@ -347,6 +734,10 @@ pub async fn run_wry(main_module_path: &str, assets: Option<EmbeddedAssets>) ->
                 id = cell.replace_with(|&mut i| i + 1);
             });
 
+            if json["hideOnClose"].as_bool().unwrap_or(false) {
+                HIDE_ON_CLOSE.with(|cell| cell.borrow_mut().insert(id));
+            }
+
             return WEBVIEW_MAP.with(|cell| {
                 let mut webviews = cell.borrow_mut();
 
@@ -367,18 +758,120 @@ pub async fn run_wry(main_module_path: &str, assets: Option<EmbeddedAssets>) ->
                     gtk_window.set_title("Basic example");
                     gtk_window.show_all();
 
+                    // with a closeRequested handler the backend decides with
+                    // `wry_close`, it may ask the user to save their work first
                     gtk_window.connect_delete_event(move |_window, _event| {
-                        STACK_MAP.with(|cell| {
-                            let mut stack_map = cell.borrow_mut();
-                            if let Some(stack) = stack_map.get_mut(&id) {
-                                stack.push(Event::Close);
+                        if intercepts_close(id) {
+                            push_event(id, Event::CloseRequested);
+                        } else if let Err(err) = close_webview(id) {
+                            eprintln!("{:#}", err);
+                        }
+                        Inhibit(true)
+                    });
+
+                    // configure-event is emitted for every move and resize,
+                    // only report what actually changed
+                    let last_size = std::cell::Cell::new(gtk_window.get_size());
+                    let last_position = std::cell::Cell::new(gtk_window.get_position());
+                    gtk_window.connect_configure_event(move |_window, event| {
+                        let (width, height) = event.get_size();
+                        let size = (width as i32, height as i32);
+                        if last_size.replace(size) != size {
+                            push_event(id, Event::Resized { width, height });
+                        }
+                        let (x, y) = event.get_position();
+                        if last_position.replace((x, y)) != (x, y) {
+                            push_event(id, Event::Moved { x, y });
+                        }
+                        false
+                    });
+
+                    gtk_window.connect_focus_in_event(move |_window, _event| {
+                        push_event(id, Event::Focused);
+                        Inhibit(false)
+                    });
+
+                    gtk_window.connect_focus_out_event(move |_window, _event| {
+                        push_event(id, Event::Blurred);
+                        Inhibit(false)
+                    });
+
+                    gtk_window.connect_window_state_event(move |_window, event| {
+                        if event
+                            .get_changed_mask()
+                            .contains(gdk::WindowState::ICONIFIED)
+                        {
+                            if event
+                                .get_new_window_state()
+                                .contains(gdk::WindowState::ICONIFIED)
+                            {
+                                push_event(id, Event::Minimized);
                             } else {
-                                panic!("Could not find stack with id {} to push onto stack", id);
+                                push_event(id, Event::Restored);
                             }
-                        });
+                        }
+                        Inhibit(false)
+                    });
+
+                    gtk_window.connect_property_scale_factor_notify(move |window| {
+                        push_event(
+                            id,
+                            Event::ScaleFactorChanged {
+                                scale_factor: window.get_scale_factor() as f64,
+                            },
+                        );
+                    });
+
+                    // key events reach the toplevel before the focused webview
+                    gtk_window.connect_key_press_event(move |_window, event| {
+                        if shortcut::handle_key_press(id, event) {
+                            return Inhibit(true);
+                        }
+                        push_event(
+                            id,
+                            helpers::keyboard_event(event, event::ElementState::Pressed),
+                        );
                         Inhibit(false)
                     });
 
+                    gtk_window.connect_key_release_event(move |_window, event| {
+                        push_event(
+                            id,
+                            helpers::keyboard_event(event, event::ElementState::Released),
+                        );
+                        Inhibit(false)
+                    });
+
+                    if let Some(settings) = gtk::Settings::get_default() {
+                        let handlers = vec![
+                            settings.connect_property_gtk_application_prefer_dark_theme_notify(
+                                move |settings| {
+                                    push_event(
+                                        id,
+                                        Event::ThemeChanged {
+                                            theme: helpers::current_theme(settings),
+                                        },
+                                    );
+                                },
+                            ),
+                            settings.connect_property_gtk_theme_name_notify(move |settings| {
+                                push_event(
+                                    id,
+                                    Event::ThemeChanged {
+                                        theme: helpers::current_theme(settings),
+                                    },
+                                );
+                            }),
+                        ];
+                        // the settings are shared by all windows and outlive this one
+                        let handlers = std::cell::RefCell::new(handlers);
+                        gtk_window.connect_destroy(move |_window| {
+                            for handler in handlers.borrow_mut().drain(..) {
+                                settings.disconnect(handler);
+                            }
+                        });
+                    }
+
                     // save our window
                     window = Some(gtk_window);
                 });
@@ -389,54 +882,173 @@ pub async fn run_wry(main_module_path: &str, assets: Option<EmbeddedAssets>) ->
                     window = Some(Window::new(&event_loop).expect("Unable to create window"));
                 });
 
-                let webview = WebViewBuilder::new(window.expect("Window not created"))
+                // linux enforce the navigation policy natively
+                #[cfg(target_os = "linux")]
+                let navigation_policy = String::new();
+                #[cfg(not(target_os = "linux"))]
+                let navigation_policy = policy.script();
+                #[cfg(not(target_os = "linux"))]
+                let rpc_policy = policy.clone();
+
+                let url = format!("wry://{}", url);
+                let resolver = move |request: &Request| {
+                    file_resolver(request, &root_file_name, assets.as_ref(), &asset_options)
+                };
+
+                let builder = WebViewBuilder::new(window.expect("Window not created"))
                     .unwrap()
                     // inject a DOMContentLoaded listener to send a RPC request
                     .initialize_script(
                         format!(
                             r#"
                                 {dom_loader}
+                                {page_events}
+                                {navigation_events}
+                                {navigation_policy}
                             "#,
                             dom_loader = include_str!("scripts/dom_loader.js"),
+                            page_events = include_str!("scripts/page_events.js"),
+                            // linux get these from webkit directly
+                            navigation_events = if cfg!(target_os = "linux") {
+                                ""
+                            } else {
+                                include_str!("scripts/navigation_events.js")
+                            },
+                            navigation_policy = navigation_policy,
                         )
                         .as_str(),
                     )
-                    .load_url(format!("wry://{}", url).as_str())?
                     .set_rpc_handler(Box::new(move |req: RpcRequest| {
-                        // this is a sample RPC test to check if we can get everything to work together
-                        let response = None;
-                        if &req.method == "domContentLoaded" {
-                            STACK_MAP.with(|cell| {
-                                let mut stack_map = cell.borrow_mut();
-                                if let Some(stack) = stack_map.get_mut(&id) {
-                                    stack.push(Event::DomContentLoaded);
-                                } else {
-                                    panic!(
-                                        "Could not find stack with id {} to push onto stack",
-                                        id
-                                    );
+                        // page events sent by our injected scripts
+                        if let Some(event) = Event::from_rpc(&req) {
+                            // linux enforces the policy natively, any page
+                            // could send this to open the browser at will
+                            #[cfg(target_os = "linux")]
+                            if let Event::NavigationBlocked { .. } = &event {
+                                return None;
+                            }
+                            #[cfg(not(target_os = "linux"))]
+                            if let Event::NavigationBlocked { url } = &event {
+                                // set by the script for links clicked in the main frame
+                                let external = req
+                                    .params
+                                    .as_ref()
+                                    .and_then(|params| params.get(0))
+                                    .and_then(|param| param.get("external"))
+                                    .and_then(|external| external.as_bool())
+                                    .unwrap_or(false);
+                                if rpc_policy.open_external && external {
+                                    if let Err(err) = navigation::open_external(url) {
+                                        eprintln!("Unable to open {}: {}", url, err);
+                                    }
                                 }
-                            });
+                            }
+                            push_event(id, event);
                         }
-                        response
+                        None
                     }))
-                    .register_protocol(
-                        "wry".into(),
-                        Box::new(move |a: &str| {
-                            file_resolver(
-                                &a.replace("wry://", ""),
-                                root_entry_point.file_name().unwrap().to_str().unwrap(),
-                                root_path.clone(),
-                                assets.clone(),
-                            )
-                            .map_err(|_| wry::Error::InitScriptError)
-                        }),
-                    )
-                    .build()?;
+                    .set_file_drop_handler(Box::new(move |event: FileDropEvent| {
+                        push_event(
+                            id,
+                            match event {
+                                FileDropEvent::Hovered(paths) => Event::FileHovered { paths },
+                                FileDropEvent::Dropped(paths) => Event::FileDropped { paths },
+                                FileDropEvent::Cancelled => Event::FileHoverCancelled,
+                            },
+                        );
+                        // let the page handle the drop as well
+                        false
+                    }));
+
+                // linux serve our protocol from webkit directly, see `protocol::register`
+                #[cfg(not(target_os = "linux"))]
+                let builder = builder.load_url(&url)?.register_protocol(
+                    "wry".into(),
+                    Box::new(move |uri: &str| {
+                        // wry only takes the body: the status and headers are lost,
+                        // missing assets are served as a 200 with the 404 page and the
+                        // MIME type is sniffed from the content and the url suffix.
+                        // Without request headers assets are read whole, media can't
+                        // be fetched by range
+                        resolver(&Request::new(uri))
+                            .and_then(|response| Ok(response.body.into_bytes()?))
+                            .map_err(|err| {
+                                wry::Error::Io(std::io::Error::new(
+                                    std::io::ErrorKind::Other,
+                                    err.to_string(),
+                                ))
+                            })
+                    }),
+                );
+
+                let webview = builder.build()?;
+
+                // mouse events are consumed by webkit, so we listen on the view itself,
+                // it also gives us the page lifecycle
+                #[cfg(target_os = "linux")]
+                if let Some(view) = helpers::webkit_view(&webview) {
+                    view.connect_button_press_event(move |_view, event| {
+                        push_event(
+                            id,
+                            helpers::mouse_event(event, event::ElementState::Pressed),
+                        );
+                        Inhibit(false)
+                    });
+                    view.connect_button_release_event(move |_view, event| {
+                        push_event(
+                            id,
+                            helpers::mouse_event(event, event::ElementState::Released),
+                        );
+                        Inhibit(false)
+                    });
+                    view.connect_scroll_event(move |_view, event| {
+                        push_event(id, helpers::wheel_event(event));
+                        Inhibit(false)
+                    });
+
+                    view.connect_load_changed(move |view, load_event| {
+                        let url = view
+                            .get_uri()
+                            .map(|uri| uri.to_string())
+                            .unwrap_or_default();
+                        match load_event {
+                            LoadEvent::Started => push_event(id, Event::NavigationStarted { url }),
+                            LoadEvent::Finished => {
+                                push_event(id, Event::NavigationFinished { url })
+                            }
+                            _ => {}
+                        }
+                    });
+                    view.connect_property_title_notify(move |view| {
+                        if let Some(title) = view.get_title() {
+                            push_event(
+                                id,
+                                Event::TitleChanged {
+                                    title: title.to_string(),
+                                },
+                            );
+                        }
+                    });
+                    view.connect_web_process_crashed(move |_view| {
+                        push_event(id, Event::PageCrashed);
+                        false
+                    });
+
+                    navigation::connect_policy(id, &view, policy);
+                    dialog::connect_script_dialogs(&view);
+                    menu::connect_context_menu(id, &view);
+
+                    protocol::register(&view, "wry", resolver);
+                    for scheme in &protocols {
+                        protocol::register_backend(id, &view, scheme);
+                    }
+                    view.load_uri(&url);
+                }
 
                 webviews.insert(id, webview);
                 STACK_MAP.with(|cell| {
-                    cell.borrow_mut().insert(id, Vec::new());
+                    let pending = PENDING_EVENTS.with(|cell| cell.borrow_mut().split_off(0));
+                    cell.borrow_mut().insert(id, pending);
                 });
 
                 // Set status to Initialized
@@ -450,10 +1062,253 @@ pub async fn run_wry(main_module_path: &str, assets: Option<EmbeddedAssets>) ->
         }),
     );
 
+    worker.js_runtime.register_op(
+        "wry_navigate",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let url = str_arg(&json, "url")?;
+            with_webview(id, |webview| navigation::navigate(id, webview, url))?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_load_html",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let html = str_arg(&json, "html")?;
+            with_webview(id, |webview| navigation::load_html(webview, html))?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_reload",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            with_webview(id, navigation::reload)?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_go_back",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            with_webview(id, navigation::go_back)?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_go_forward",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            with_webview(id, navigation::go_forward)?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_protocol_respond",
+        json_op_sync(move |_state, json: Value, zero_copy| {
+            let request_id = u64_arg(&json, "requestId")?;
+            let status = json["status"].as_u64().unwrap_or(200) as u16;
+            let headers: HashMap<String, String> =
+                serde_json::from_value(json["headers"].clone()).unwrap_or_default();
+            let body = zero_copy
+                .first()
+                .map(|body| body.to_vec())
+                .unwrap_or_default();
+            protocol::respond(
+                request_id,
+                Response {
+                    status,
+                    headers: headers.into_iter().collect(),
+                    body: Body::Bytes(body),
+                },
+            )?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_dialog_file",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let kind: FileDialogKind = serde_json::from_value(json["kind"].clone())?;
+            let options: FileDialogOptions = serde_json::from_value(json.clone())?;
+            let request_id = with_webview(id, |webview| {
+                dialog::file_dialog(id, webview, kind, options)
+            })?;
+            Ok(json!(request_id))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_dialog_message",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let options: MessageOptions = serde_json::from_value(json.clone())?;
+            let request_id =
+                with_webview(id, |webview| dialog::message_dialog(id, webview, options))?;
+            Ok(json!(request_id))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_set_menu",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let items: Vec<MenuItem> = serde_json::from_value(json["items"].clone())?;
+            with_webview(id, |webview| menu::set_menu(id, webview, &items))?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_set_context_menu",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let items: Vec<MenuItem> = serde_json::from_value(json["items"].clone())?;
+            menu::set_context_menu(id, &items)?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_remove_tray",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            tray::remove_tray(id);
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_close",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            Ok(json!(close_webview(id)?))
+        }),
+    );
+
+    // closing the window sends closeRequested instead of closing it
+    worker.js_runtime.register_op(
+        "wry_intercept_close",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            INTERCEPT_CLOSE.with(|cell| cell.borrow_mut().insert(id));
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_register_shortcut",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let accelerator = str_arg(&json, "accelerator")?;
+            let global = json["global"].as_bool().unwrap_or(false);
+            shortcut::register(id, accelerator, global)?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_unregister_shortcut",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let accelerator = str_arg(&json, "accelerator")?;
+            shortcut::unregister(id, accelerator);
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_set_visible",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let id = u64_arg(&json, "id")?;
+            let visible = json["visible"].as_bool().unwrap_or(true);
+            with_webview(id, |webview| {
+                helpers::set_visible(webview, visible);
+                Ok(())
+            })?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_clipboard_read",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            match json["format"].as_str().unwrap_or("text") {
+                "text" => Ok(json!(clipboard::read_text()?)),
+                "html" => Ok(json!(clipboard::read_html()?)),
+                format => Err(anyhow!("Unknown clipboard format {}", format)),
+            }
+        }),
+    );
+
+    // PNG bytes, empty when there is no image
+    worker.js_runtime.register_op(
+        "wry_clipboard_read_image",
+        bin_op_sync(move |_state, _arg, _zero_copy| {
+            Ok(clipboard::read_image()?.unwrap_or_default())
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_clipboard_write",
+        json_op_sync(move |_state, json: Value, zero_copy| {
+            match json["format"].as_str().unwrap_or("text") {
+                "text" => clipboard::write_text(json["text"].as_str().unwrap_or_default())?,
+                "html" => clipboard::write_html(
+                    json["html"].as_str().unwrap_or_default(),
+                    json["text"].as_str().unwrap_or_default(),
+                )?,
+                "image" => {
+                    let bytes = zero_copy
+                        .first()
+                        .ok_or_else(|| type_error("Missing image bytes"))?;
+                    clipboard::write_image(bytes)?
+                }
+                format => return Err(anyhow!("Unknown clipboard format {}", format)),
+            }
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_notification_close",
+        json_op_sync(move |_state, json: Value, _zero_copy| {
+            let notification_id = u64_arg(&json, "id")? as u32;
+            notification::close(notification_id)?;
+            Ok(json!(null))
+        }),
+    );
+
+    worker.js_runtime.register_op(
+        "wry_notification_events",
+        json_op_sync(move |_state, _json: Value, _zero_copy| {
+            Ok(json!(notification::take_events()))
+        }),
+    );
+
     // inject webview.js
     worker
         .js_runtime
         .execute("<webview>", include_str!("scripts/webview.js"))?;
+    worker
+        .js_runtime
+        .execute("<assets>", include_str!("scripts/assets.js"))?;
+    worker
+        .js_runtime
+        .execute("<dialog>", include_str!("scripts/dialog.js"))?;
+    worker
+        .js_runtime
+        .execute("<clipboard>", include_str!("scripts/clipboard.js"))?;
+    worker
+        .js_runtime
+        .execute("<notification>", include_str!("scripts/notification.js"))?;
 
     worker.bootstrap(&options);
     worker.execute_module(&main_module).await?;
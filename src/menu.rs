@@ -0,0 +1,222 @@
+use serde::Deserialize;
+use wry::webview::WebView;
+
+#[cfg(target_os = "linux")]
+use crate::{accelerator::Accelerator, event::Event};
+#[cfg(target_os = "linux")]
+use gtk::prelude::*;
+#[cfg(target_os = "linux")]
+use std::{cell::RefCell, collections::HashMap};
+#[cfg(target_os = "linux")]
+use webkit2gtk::WebViewExt;
+
+#[cfg(target_os = "linux")]
+thread_local! {
+  // menu bar of each webview window, replaced by the next `setMenu`
+  static MENU_BARS: RefCell<HashMap<u64, (gtk::MenuBar, gtk::AccelGroup)>> = RefCell::new(HashMap::new());
+  static CONTEXT_MENUS: RefCell<HashMap<u64, gtk::Menu>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MenuItemKind {
+    Normal,
+    Separator,
+    Checkbox,
+}
+
+impl Default for MenuItemKind {
+    fn default() -> Self {
+        MenuItemKind::Normal
+    }
+}
+
+/// Menu entry as declared by the backend script.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MenuItem {
+    /// sent back with `menuItemClicked`
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default, rename = "type")]
+    pub kind: MenuItemKind,
+    /// `CmdOrCtrl+Shift+S` style shortcut
+    #[serde(default)]
+    pub accelerator: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub checked: bool,
+    #[serde(default)]
+    pub submenu: Option<Vec<MenuItem>>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Replace the menu bar of the webview window, an empty menu removes it.
+#[cfg(target_os = "linux")]
+pub fn set_menu(id: u64, webview: &WebView, items: &[MenuItem]) -> crate::Result<()> {
+    let window = webview.window();
+
+    MENU_BARS.with(|cell| {
+        let mut menu_bars = cell.borrow_mut();
+
+        // the view is packed below the menu bar the first time
+        let container = match menu_bars.remove(&id) {
+            Some((menu_bar, accel_group)) => {
+                window.remove_accel_group(&accel_group);
+                let container = menu_bar
+                    .get_parent()
+                    .and_then(|parent| parent.downcast::<gtk::Box>().ok());
+                if let Some(container) = &container {
+                    container.remove(&menu_bar);
+                }
+                container
+            }
+            None if items.is_empty() => None,
+            None => {
+                let child = window
+                    .get_child()
+                    .ok_or_else(|| anyhow::anyhow!("Webview window has no content"))?;
+                window.remove(&child);
+                let container = gtk::Box::new(gtk::Orientation::Vertical, 0);
+                container.pack_start(&child, true, true, 0);
+                window.add(&container);
+                Some(container)
+            }
+        };
+
+        let container = match container {
+            Some(container) if !items.is_empty() => container,
+            _ => return Ok(()),
+        };
+
+        let accel_group = gtk::AccelGroup::new();
+        let menu_bar = gtk::MenuBar::new();
+        for item in items {
+            menu_bar.append(&build_item(id, item, Some(&accel_group))?);
+        }
+        container.pack_start(&menu_bar, false, false, 0);
+        container.reorder_child(&menu_bar, 0);
+        window.add_accel_group(&accel_group);
+        window.show_all();
+
+        menu_bars.insert(id, (menu_bar, accel_group));
+        Ok(())
+    })
+}
+
+/// Replace the context menu of the page, an empty menu restores the
+/// default one.
+#[cfg(target_os = "linux")]
+pub fn set_context_menu(id: u64, items: &[MenuItem]) -> crate::Result<()> {
+    if items.is_empty() {
+        CONTEXT_MENUS.with(|cell| cell.borrow_mut().remove(&id));
+        return Ok(());
+    }
+
+    let menu = build_menu(id, items)?;
+    CONTEXT_MENUS.with(|cell| cell.borrow_mut().insert(id, menu));
+    Ok(())
+}
+
+/// Popup menu whose clicks are reported to the given webview.
+#[cfg(target_os = "linux")]
+pub fn build_menu(id: u64, items: &[MenuItem]) -> crate::Result<gtk::Menu> {
+    let menu = gtk::Menu::new();
+    for item in items {
+        menu.append(&build_item(id, item, None)?);
+    }
+    menu.show_all();
+    Ok(menu)
+}
+
+/// Show our context menu instead of the webkit one when there is one.
+#[cfg(target_os = "linux")]
+pub fn connect_context_menu(id: u64, view: &webkit2gtk::WebView) {
+    view.connect_context_menu(move |_view, _context_menu, event, _hit_test| {
+        CONTEXT_MENUS.with(|cell| match cell.borrow().get(&id) {
+            Some(menu) => {
+                menu.popup_easy(3, event.get_time());
+                true
+            }
+            None => false,
+        })
+    });
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_menu(_id: u64, _webview: &WebView, _items: &[MenuItem]) -> crate::Result<()> {
+    Err(anyhow::anyhow!(
+        "Menus are not supported on this platform yet"
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_context_menu(_id: u64, _items: &[MenuItem]) -> crate::Result<()> {
+    Err(anyhow::anyhow!(
+        "Context menus are not supported on this platform yet"
+    ))
+}
+
+/// Accelerators are only active with an accel group, context menus only
+/// display them.
+#[cfg(target_os = "linux")]
+fn build_item(
+    id: u64,
+    item: &MenuItem,
+    accel_group: Option<&gtk::AccelGroup>,
+) -> crate::Result<gtk::MenuItem> {
+    let menu_item: gtk::MenuItem = match item.kind {
+        MenuItemKind::Separator => return Ok(gtk::SeparatorMenuItem::new().upcast()),
+        MenuItemKind::Checkbox => {
+            let check_item = gtk::CheckMenuItem::with_mnemonic(&item.label);
+            check_item.set_active(item.checked);
+            check_item.upcast()
+        }
+        MenuItemKind::Normal => gtk::MenuItem::with_mnemonic(&item.label),
+    };
+    menu_item.set_sensitive(item.enabled);
+
+    if let Some(accelerator) = &item.accelerator {
+        let (key, modifiers) = accelerator.parse::<Accelerator>()?.to_gtk();
+        let accel_group = accel_group.cloned().unwrap_or_else(gtk::AccelGroup::new);
+        menu_item.add_accelerator(
+            "activate",
+            &accel_group,
+            key,
+            modifiers,
+            gtk::AccelFlags::VISIBLE,
+        );
+    }
+
+    if let Some(submenu) = &item.submenu {
+        let menu = gtk::Menu::new();
+        if let Some(accel_group) = accel_group {
+            menu.set_accel_group(Some(accel_group));
+        }
+        for child in submenu {
+            menu.append(&build_item(id, child, accel_group)?);
+        }
+        menu_item.set_submenu(Some(&menu));
+    } else if let Some(item_id) = item.id.clone() {
+        menu_item.connect_activate(move |menu_item| {
+            let checked = menu_item
+                .downcast_ref::<gtk::CheckMenuItem>()
+                .map(|check_item| check_item.get_active());
+            crate::push_event(
+                id,
+                Event::MenuItemClicked {
+                    id: item_id.clone(),
+                    checked,
+                },
+            );
+        });
+    }
+
+    Ok(menu_item)
+}
@@ -0,0 +1,335 @@
+use deno_core::url::Url;
+use serde::Deserialize;
+use wry::webview::WebView;
+
+#[cfg(target_os = "linux")]
+use crate::event::Event;
+#[cfg(target_os = "linux")]
+use glib::Cast;
+#[cfg(target_os = "linux")]
+use std::{cell::RefCell, collections::HashMap};
+#[cfg(target_os = "linux")]
+use webkit2gtk::{
+    NavigationPolicyDecision, NavigationPolicyDecisionExt, NavigationType, PolicyDecisionExt,
+    PolicyDecisionType, URIRequestExt, WebViewExt,
+};
+
+#[cfg(target_os = "linux")]
+thread_local! {
+  // url requested by the backend, allowed whatever the policy
+  static PENDING_NAVIGATION: RefCell<HashMap<u64, String>> = RefCell::new(HashMap::new());
+}
+
+/// Which urls the webview is allowed to navigate to, everything else is
+/// blocked and reported with a `navigationBlocked` event.
+///
+/// Outside of linux the policy is enforced by a script that only sees link
+/// clicks and `window.open`, assigning `location.href` isn't blocked there.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigationPolicy {
+    /// Schemes (`"https:"`, `"data:"`) or origins (`"https://tauri.studio"`)
+    /// allowed on top of our own `wry://` assets.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Open blocked links clicked by the user in the system browser, only
+    /// for `http`, `https` and `mailto` urls.
+    #[serde(default = "default_open_external")]
+    pub open_external: bool,
+}
+
+fn default_open_external() -> bool {
+    true
+}
+
+impl Default for NavigationPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            open_external: default_open_external(),
+        }
+    }
+}
+
+impl NavigationPolicy {
+    pub fn is_allowed(&self, url: &str) -> bool {
+        let url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(_) => return false,
+        };
+
+        if matches!(url.scheme(), "wry" | "about") {
+            return true;
+        }
+
+        self.allowed_origins.iter().any(|allowed| {
+            let allowed = allowed.trim_end_matches('/');
+            if let Some(scheme) = allowed.strip_suffix(':') {
+                url.scheme() == scheme
+            } else {
+                Url::parse(allowed)
+                    .map(|allowed| allowed.origin() == url.origin())
+                    .unwrap_or(false)
+            }
+        })
+    }
+
+    /// Javascript used on platforms where we can't intercept navigations
+    /// natively, blocked links are reported with a `navigationBlocked` RPC.
+    #[cfg(not(target_os = "linux"))]
+    pub fn script(&self) -> String {
+        format!(
+            r#"
+                window.____wryAllowedOrigins = {allowed_origins};
+                {navigation_policy}
+            "#,
+            allowed_origins = serde_json::to_string(&self.allowed_origins).unwrap(),
+            navigation_policy = include_str!("scripts/navigation_policy.js"),
+        )
+    }
+}
+
+/// Enforce the policy on every navigation of the view.
+#[cfg(target_os = "linux")]
+pub fn connect_policy(id: u64, view: &webkit2gtk::WebView, policy: NavigationPolicy) {
+    view.connect_decide_policy(move |_view, decision, decision_type| {
+        match decision_type {
+            PolicyDecisionType::NavigationAction | PolicyDecisionType::NewWindowAction => {}
+            _ => return false,
+        };
+
+        let action = match decision
+            .clone()
+            .downcast::<NavigationPolicyDecision>()
+            .ok()
+            .and_then(|decision| decision.get_navigation_action())
+        {
+            Some(action) => action,
+            None => return false,
+        };
+        let url = match action.get_request().and_then(|request| request.get_uri()) {
+            Some(url) => url.to_string(),
+            None => return false,
+        };
+
+        // the next navigation is the one we started, whatever it is the
+        // pending url is used up
+        let requested = PENDING_NAVIGATION
+            .with(|cell| cell.borrow_mut().remove(&id))
+            .map_or(false, |pending| pending == normalize_url(&url));
+
+        if requested || policy.is_allowed(&url) {
+            return false;
+        }
+
+        decision.ignore();
+        // webkit doesn't tell which frame navigates, but subframes can only
+        // show allowed pages so a link clicked there is trusted as well
+        let clicked = action.is_user_gesture()
+            && (decision_type == PolicyDecisionType::NewWindowAction
+                || action.get_navigation_type() == NavigationType::LinkClicked);
+        if policy.open_external && clicked && is_external(&url) {
+            if let Err(err) = open_external(&url) {
+                eprintln!("Unable to open {}: {}", url, err);
+            }
+        }
+        crate::push_event(id, Event::NavigationBlocked { url });
+        true
+    });
+}
+
+pub fn navigate(id: u64, webview: &mut WebView, url: &str) -> crate::Result<()> {
+    let url = resolve_url(url);
+
+    #[cfg(target_os = "linux")]
+    {
+        // webkit hands us the normalized url in decide-policy
+        PENDING_NAVIGATION.with(|cell| cell.borrow_mut().insert(id, normalize_url(&url)));
+        webkit_view(webview)?.load_uri(&url);
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = id;
+        crate::helpers::eval_script(
+            webview,
+            &format!("window.location.href = {};", serde_json::to_string(&url)?),
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn load_html(webview: &mut WebView, html: &str) -> crate::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let view = webkit_view(webview)?;
+        // keep the current page as base so relative assets still resolve
+        let base_uri = view.get_uri().map(|uri| uri.to_string());
+        view.load_html(html, base_uri.as_deref());
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    crate::helpers::eval_script(
+        webview,
+        &format!(
+            "document.open(); document.write({}); document.close();",
+            serde_json::to_string(html)?
+        ),
+    )?;
+
+    Ok(())
+}
+
+pub fn reload(webview: &mut WebView) -> crate::Result<()> {
+    #[cfg(target_os = "linux")]
+    webkit_view(webview)?.reload();
+
+    #[cfg(not(target_os = "linux"))]
+    crate::helpers::eval_script(webview, "window.location.reload();")?;
+
+    Ok(())
+}
+
+pub fn go_back(webview: &mut WebView) -> crate::Result<()> {
+    #[cfg(target_os = "linux")]
+    webkit_view(webview)?.go_back();
+
+    #[cfg(not(target_os = "linux"))]
+    crate::helpers::eval_script(webview, "window.history.back();")?;
+
+    Ok(())
+}
+
+pub fn go_forward(webview: &mut WebView) -> crate::Result<()> {
+    #[cfg(target_os = "linux")]
+    webkit_view(webview)?.go_forward();
+
+    #[cfg(not(target_os = "linux"))]
+    crate::helpers::eval_script(webview, "window.history.forward();")?;
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn webkit_view(webview: &WebView) -> crate::Result<webkit2gtk::WebView> {
+    crate::helpers::webkit_view(webview).ok_or_else(|| anyhow::anyhow!("Webkit view not found"))
+}
+
+/// Prefix relative urls with our asset protocol.
+pub fn resolve_url(url: &str) -> String {
+    if url.contains(':') {
+        url.to_string()
+    } else {
+        format!("wry://{}", url)
+    }
+}
+
+/// Url as webkit reports it, `https://tauri.studio` is `https://tauri.studio/`.
+#[cfg(target_os = "linux")]
+pub fn normalize_url(url: &str) -> String {
+    Url::parse(url)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+/// Whether the url can be handed to another app, we never let pages
+/// launch files or custom schemes.
+pub fn is_external(url: &str) -> bool {
+    Url::parse(url)
+        .map(|url| matches!(url.scheme(), "http" | "https" | "mailto"))
+        .unwrap_or(false)
+}
+
+/// Open the url with the default handler of the system.
+pub fn open_external(url: &str) -> crate::Result<()> {
+    if !is_external(url) {
+        return Err(anyhow::anyhow!("Refusing to open {} externally", url));
+    }
+
+    #[cfg(target_os = "linux")]
+    gio::AppInfo::launch_default_for_uri(url, None::<&gio::AppLaunchContext>)?;
+
+    #[cfg(target_os = "macos")]
+    std::process::Command::new("open").arg(url).spawn()?;
+
+    #[cfg(target_os = "windows")]
+    std::process::Command::new("explorer").arg(url).spawn()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed_origins: &[&str]) -> NavigationPolicy {
+        NavigationPolicy {
+            allowed_origins: allowed_origins
+                .iter()
+                .map(|origin| origin.to_string())
+                .collect(),
+            open_external: false,
+        }
+    }
+
+    #[test]
+    fn allows_our_assets_only_by_default() {
+        let policy = NavigationPolicy::default();
+        assert!(policy.is_allowed("wry://index.html"));
+        assert!(policy.is_allowed("about:blank"));
+        assert!(!policy.is_allowed("data:text/html,<script>alert(1)</script>"));
+        assert!(!policy.is_allowed("https://tauri.studio"));
+        assert!(!policy.is_allowed("file:///etc/passwd"));
+        assert!(!policy.is_allowed("not a url"));
+    }
+
+    #[test]
+    fn allows_whole_schemes() {
+        let policy = policy(&["https:", "data:"]);
+        assert!(policy.is_allowed("https://tauri.studio/docs"));
+        assert!(policy.is_allowed("data:text/plain,hello"));
+        assert!(!policy.is_allowed("http://tauri.studio"));
+    }
+
+    #[test]
+    fn allows_exact_origins() {
+        let policy = policy(&["https://tauri.studio/", "http://localhost:8080"]);
+        assert!(policy.is_allowed("https://tauri.studio/docs?page=1"));
+        assert!(policy.is_allowed("http://localhost:8080/app"));
+        assert!(!policy.is_allowed("http://tauri.studio"));
+        assert!(!policy.is_allowed("https://tauri.studio.evil.com"));
+        assert!(!policy.is_allowed("https://evil.com/https://tauri.studio"));
+        assert!(!policy.is_allowed("http://localhost:8081"));
+    }
+
+    #[test]
+    fn ignores_invalid_allowed_origins() {
+        let policy = policy(&["tauri.studio", ""]);
+        assert!(!policy.is_allowed("https://tauri.studio"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn normalizes_urls_like_webkit() {
+        assert_eq!(
+            normalize_url("https://tauri.studio"),
+            "https://tauri.studio/"
+        );
+        assert_eq!(
+            normalize_url("HTTPS://Tauri.Studio:443/a/../docs"),
+            "https://tauri.studio/docs"
+        );
+        assert_eq!(normalize_url("wry://index.html"), "wry://index.html");
+        assert_eq!(normalize_url("not a url"), "not a url");
+    }
+
+    #[test]
+    fn opens_only_web_and_mail_links_externally() {
+        assert!(is_external("https://tauri.studio"));
+        assert!(is_external("mailto:hello@tauri.studio"));
+        assert!(!is_external("file:///etc/passwd"));
+        assert!(!is_external("smb://server/share"));
+        assert!(!is_external("javascript:alert(1)"));
+    }
+}
@@ -0,0 +1,546 @@
+use serde::Deserialize;
+
+use crate::assets::Assets;
+use crate::event::Event;
+
+#[cfg(target_os = "linux")]
+use gio::{BusType, DBusCallFlags, DBusConnection, DBusSignalFlags};
+#[cfg(target_os = "linux")]
+use glib::{
+    translate::{from_glib_full, from_glib_none, ToGlibPtr},
+    ToVariant, Variant, VariantDict, VariantTy,
+};
+#[cfg(target_os = "linux")]
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fs::{DirBuilder, OpenOptions},
+    io::Write,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+#[cfg(target_os = "linux")]
+const BUS_NAME: &str = "org.freedesktop.Notifications";
+#[cfg(target_os = "linux")]
+const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+/// action invoked when the notification itself is clicked
+#[cfg(target_os = "linux")]
+const DEFAULT_ACTION: &str = "default";
+
+#[cfg(target_os = "linux")]
+thread_local! {
+  static CONNECTION: RefCell<Option<DBusConnection>> = RefCell::new(None);
+  // notifications we sent, the server signals them for every app
+  static PENDING: RefCell<HashSet<u32>> = RefCell::new(HashSet::new());
+  static EVENTS: RefCell<Vec<Event>> = RefCell::new(Vec::new());
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotificationAction {
+    pub id: String,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationOptions {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    /// path of an app asset, or an icon name of the desktop theme
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// buttons shown by servers supporting them
+    #[serde(default)]
+    pub actions: Vec<NotificationAction>,
+    /// milliseconds, the server decides when not set
+    #[serde(default)]
+    pub timeout: Option<i32>,
+}
+
+/// Where notifications are sent, the session bus or a stand-in in tests.
+#[cfg(target_os = "linux")]
+trait NotificationServer {
+    fn call(
+        &self,
+        method: &str,
+        parameters: &Variant,
+        reply_type: Option<&str>,
+    ) -> crate::Result<Variant>;
+}
+
+#[cfg(target_os = "linux")]
+impl NotificationServer for DBusConnection {
+    fn call(
+        &self,
+        method: &str,
+        parameters: &Variant,
+        reply_type: Option<&str>,
+    ) -> crate::Result<Variant> {
+        Ok(self.call_sync(
+            Some(BUS_NAME),
+            OBJECT_PATH,
+            BUS_NAME,
+            method,
+            Some(parameters),
+            reply_type.and_then(|reply_type| VariantTy::new(reply_type).ok()),
+            DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+        )?)
+    }
+}
+
+/// Send a notification to the freedesktop notification server of the
+/// session bus, the returned id is carried by its events.
+#[cfg(target_os = "linux")]
+pub fn notify(options: &NotificationOptions, assets: &dyn Assets) -> crate::Result<u32> {
+    send(&connection()?, options, assets)
+}
+
+#[cfg(target_os = "linux")]
+pub fn close(notification_id: u32) -> crate::Result<()> {
+    withdraw(&connection()?, notification_id)
+}
+
+#[cfg(target_os = "linux")]
+fn send(
+    server: &dyn NotificationServer,
+    options: &NotificationOptions,
+    assets: &dyn Assets,
+) -> crate::Result<u32> {
+    let app_name = std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default();
+    let icon = match &options.icon {
+        Some(icon) => icon_path(icon, assets)?,
+        None => String::new(),
+    };
+    let mut actions = vec![DEFAULT_ACTION.to_string(), String::new()];
+    for action in &options.actions {
+        actions.push(action.id.clone());
+        actions.push(action.label.clone());
+    }
+
+    // (susssasa{sv}i)
+    let parameters = tuple(&[
+        app_name.to_variant(),
+        0u32.to_variant(),
+        icon.to_variant(),
+        options.title.to_variant(),
+        options.body.to_variant(),
+        string_array(&actions),
+        VariantDict::new(None).end(),
+        options.timeout.unwrap_or(-1).to_variant(),
+    ]);
+    let reply = server.call("Notify", &parameters, Some("(u)"))?;
+    let notification_id = child(&reply, 0)
+        .get::<u32>()
+        .ok_or_else(|| anyhow::anyhow!("Invalid reply of the notification server"))?;
+
+    PENDING.with(|cell| cell.borrow_mut().insert(notification_id));
+    Ok(notification_id)
+}
+
+#[cfg(target_os = "linux")]
+fn withdraw(server: &dyn NotificationServer, notification_id: u32) -> crate::Result<()> {
+    server.call(
+        "CloseNotification",
+        &tuple(&[notification_id.to_variant()]),
+        None,
+    )?;
+    Ok(())
+}
+
+/// Clicks and closes of our notifications since the last call.
+#[cfg(target_os = "linux")]
+pub fn take_events() -> Vec<Event> {
+    // signals are dispatched by the main context, which only runs with a
+    // webview loop
+    glib::MainContext::default().iteration(false);
+    EVENTS.with(|cell| cell.borrow_mut().split_off(0))
+}
+
+#[cfg(target_os = "linux")]
+fn connection() -> crate::Result<DBusConnection> {
+    CONNECTION.with(|cell| {
+        if let Some(connection) = &*cell.borrow() {
+            return Ok(connection.clone());
+        }
+
+        let connection = gio::bus_get_sync(BusType::Session, None::<&gio::Cancellable>)?;
+        connection.signal_subscribe(
+            Some(BUS_NAME),
+            Some(BUS_NAME),
+            None,
+            Some(OBJECT_PATH),
+            None,
+            DBusSignalFlags::NONE,
+            |_connection, _sender, _path, _interface, signal, parameters| {
+                if let Some(event) = signal_event(signal, parameters) {
+                    EVENTS.with(|cell| cell.borrow_mut().push(event));
+                }
+            },
+        );
+        *cell.borrow_mut() = Some(connection.clone());
+        Ok(connection)
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn signal_event(signal: &str, parameters: &Variant) -> Option<Event> {
+    match (signal, parameters.type_().to_str()) {
+        ("ActionInvoked", "(us)") => {
+            let notification_id = child(parameters, 0).get::<u32>()?;
+            if !PENDING.with(|cell| cell.borrow().contains(&notification_id)) {
+                return None;
+            }
+            Some(Event::NotificationClicked {
+                notification_id,
+                action: child(parameters, 1)
+                    .get::<String>()
+                    .filter(|action| action != DEFAULT_ACTION),
+            })
+        }
+        ("NotificationClosed", "(uu)") => {
+            let notification_id = child(parameters, 0).get::<u32>()?;
+            if !PENDING.with(|cell| cell.borrow_mut().remove(&notification_id)) {
+                return None;
+            }
+            let reason = match child(parameters, 1).get::<u32>()? {
+                1 => "expired",
+                2 => "dismissed",
+                3 => "closed",
+                _ => "undefined",
+            };
+            Some(Event::NotificationClosed {
+                notification_id,
+                reason: reason.to_string(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Notification servers only read icons from files, assets are written to
+/// the runtime directory of the user first.
+#[cfg(target_os = "linux")]
+fn icon_path(icon: &str, assets: &dyn Assets) -> crate::Result<String> {
+    let bytes = match assets.get(icon) {
+        Some(bytes) => bytes,
+        // an icon name of the theme or an absolute path
+        None => return Ok(icon.to_string()),
+    };
+
+    // XDG_RUNTIME_DIR, or the cache directory when the session has none
+    let dir = glib::get_user_runtime_dir()
+        .ok_or_else(|| anyhow::anyhow!("No runtime directory to write the icon to"))?
+        .join("wry-icons");
+    let path = write_icon(&dir, icon, &bytes)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Write an icon named after its content in a directory only the user can
+/// read. Whatever is at its path is replaced, never followed.
+#[cfg(target_os = "linux")]
+fn write_icon(dir: &Path, icon: &str, bytes: &[u8]) -> crate::Result<PathBuf> {
+    DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+
+    let extension = Path::new(icon)
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let name = format!("{}{}", crate::asset_cache::content_hash(bytes), extension);
+    let path = dir.join(&name);
+    let written = std::fs::symlink_metadata(&path)
+        .map_or(false, |metadata| metadata.file_type().is_file())
+        && std::fs::read(&path).map_or(false, |existing| existing == bytes);
+    if written {
+        return Ok(path);
+    }
+
+    let partial = dir.join(format!(".{}.{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&partial);
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&partial)?
+        .write_all(bytes)?;
+    std::fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+// glib 0.10 only converts scalars and strings to variants
+
+#[cfg(target_os = "linux")]
+fn tuple(children: &[Variant]) -> Variant {
+    unsafe {
+        from_glib_none(glib_sys::g_variant_new_tuple(
+            children.to_glib_none().0,
+            children.len(),
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn string_array(strings: &[String]) -> Variant {
+    let children: Vec<Variant> = strings.iter().map(ToVariant::to_variant).collect();
+    unsafe {
+        from_glib_none(glib_sys::g_variant_new_array(
+            VariantTy::new("s").unwrap().to_glib_none().0,
+            children.to_glib_none().0,
+            children.len(),
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn child(variant: &Variant, index: usize) -> Variant {
+    unsafe {
+        from_glib_full(glib_sys::g_variant_get_child_value(
+            variant.to_glib_none().0,
+            index,
+        ))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn notify(_options: &NotificationOptions, _assets: &dyn Assets) -> crate::Result<u32> {
+    Err(anyhow::anyhow!(
+        "Notifications are not supported on this platform yet"
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn close(_notification_id: u32) -> crate::Result<()> {
+    Err(anyhow::anyhow!(
+        "Notifications are not supported on this platform yet"
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn take_events() -> Vec<Event> {
+    Vec::new()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn signal(signal: &str, notification_id: u32, value: Variant) -> Option<Event> {
+        signal_event(signal, &tuple(&[notification_id.to_variant(), value]))
+    }
+
+    fn sent(notification_id: u32) {
+        PENDING.with(|cell| cell.borrow_mut().insert(notification_id));
+    }
+
+    fn close_reason(reason: u32) -> Option<String> {
+        sent(1);
+        match signal("NotificationClosed", 1, reason.to_variant()) {
+            Some(Event::NotificationClosed { reason, .. }) => Some(reason),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn maps_close_reasons() {
+        assert_eq!(close_reason(1).as_deref(), Some("expired"));
+        assert_eq!(close_reason(2).as_deref(), Some("dismissed"));
+        assert_eq!(close_reason(3).as_deref(), Some("closed"));
+        assert_eq!(close_reason(4).as_deref(), Some("undefined"));
+        assert_eq!(close_reason(0).as_deref(), Some("undefined"));
+    }
+
+    #[test]
+    fn maps_clicks_to_actions() {
+        sent(2);
+        match signal("ActionInvoked", 2, DEFAULT_ACTION.to_variant()) {
+            Some(Event::NotificationClicked {
+                notification_id: 2,
+                action: None,
+            }) => {}
+            event => panic!("unexpected {:?}", event),
+        }
+        match signal("ActionInvoked", 2, "reply".to_variant()) {
+            Some(Event::NotificationClicked {
+                notification_id: 2,
+                action: Some(action),
+            }) => assert_eq!(action, "reply"),
+            event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn ignores_notifications_of_other_apps() {
+        assert!(signal("NotificationClosed", 3, 2u32.to_variant()).is_none());
+        assert!(signal("ActionInvoked", 3, DEFAULT_ACTION.to_variant()).is_none());
+
+        // closed notifications are forgotten
+        sent(4);
+        assert!(signal("NotificationClosed", 4, 2u32.to_variant()).is_some());
+        assert!(signal("NotificationClosed", 4, 2u32.to_variant()).is_none());
+    }
+
+    /// Records the calls and answers them like a notification server.
+    struct StandIn {
+        calls: RefCell<Vec<(String, Variant)>>,
+        reply: Variant,
+    }
+
+    impl StandIn {
+        fn new(reply: Variant) -> Self {
+            Self {
+                calls: RefCell::new(Vec::new()),
+                reply,
+            }
+        }
+
+        fn received(&self, index: usize) -> (String, Variant) {
+            self.calls.borrow()[index].clone()
+        }
+    }
+
+    impl NotificationServer for StandIn {
+        fn call(
+            &self,
+            method: &str,
+            parameters: &Variant,
+            _reply_type: Option<&str>,
+        ) -> crate::Result<Variant> {
+            self.calls
+                .borrow_mut()
+                .push((method.to_string(), parameters.clone()));
+            Ok(self.reply.clone())
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("wry-notification-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn string(variant: &Variant, index: usize) -> Option<String> {
+        child(variant, index).get::<String>()
+    }
+
+    #[test]
+    fn sends_notifications() {
+        let dir = temp_dir("send");
+        let assets = crate::assets::DirAssets::new(&dir).unwrap();
+        let server = StandIn::new(tuple(&[6u32.to_variant()]));
+        let options = NotificationOptions {
+            title: "Download finished".into(),
+            body: "report.pdf".into(),
+            icon: Some("document-save".into()),
+            actions: vec![NotificationAction {
+                id: "open".into(),
+                label: "Open".into(),
+            }],
+            timeout: Some(5000),
+        };
+        assert_eq!(send(&server, &options, &assets).unwrap(), 6);
+
+        let (method, parameters) = server.received(0);
+        assert_eq!(method, "Notify");
+        assert_eq!(parameters.type_().to_str(), "(susssasa{sv}i)");
+        assert_eq!(child(&parameters, 1).get::<u32>(), Some(0));
+        assert_eq!(string(&parameters, 2).as_deref(), Some("document-save"));
+        assert_eq!(string(&parameters, 3).as_deref(), Some("Download finished"));
+        assert_eq!(string(&parameters, 4).as_deref(), Some("report.pdf"));
+        let actions = child(&parameters, 5);
+        let actions: Vec<String> = (0..4).filter_map(|index| string(&actions, index)).collect();
+        assert_eq!(actions, [DEFAULT_ACTION, "", "open", "Open"]);
+        assert_eq!(child(&parameters, 7).get::<i32>(), Some(5000));
+
+        // its events are ours now
+        assert!(signal("NotificationClosed", 6, 2u32.to_variant()).is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_invalid_replies() {
+        let dir = temp_dir("reply");
+        let assets = crate::assets::DirAssets::new(&dir).unwrap();
+        let server = StandIn::new(tuple(&["7".to_variant()]));
+        assert!(send(&server, &NotificationOptions::default(), &assets).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn closes_notifications() {
+        let server = StandIn::new(tuple(&[]));
+        withdraw(&server, 8).unwrap();
+        let (method, parameters) = server.received(0);
+        assert_eq!(method, "CloseNotification");
+        assert_eq!(child(&parameters, 0).get::<u32>(), Some(8));
+    }
+
+    #[test]
+    fn sends_asset_icons_as_private_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("icon");
+        std::fs::write(dir.join("icon.png"), b"png").unwrap();
+        let assets = crate::assets::DirAssets::new(&dir).unwrap();
+        let server = StandIn::new(tuple(&[9u32.to_variant()]));
+        let options = NotificationOptions {
+            icon: Some("icon.png".into()),
+            ..Default::default()
+        };
+        send(&server, &options, &assets).unwrap();
+
+        let icon = string(&server.received(0).1, 2).unwrap();
+        let icon = Path::new(&icon);
+        assert_eq!(std::fs::read(icon).unwrap(), b"png");
+        assert!(icon.to_string_lossy().ends_with(".png"));
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(icon.parent().unwrap()), 0o700);
+        assert_eq!(mode(icon), 0o600);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn replaces_files_planted_at_the_icon_path() {
+        let dir = temp_dir("planted");
+        let icons = dir.join("icons");
+        let victim = dir.join("victim");
+        std::fs::write(&victim, b"victim").unwrap();
+        let path = write_icon(&icons, "icon.png", b"png").unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        std::os::unix::fs::symlink(&victim, &path).unwrap();
+        assert_eq!(write_icon(&icons, "icon.png", b"png").unwrap(), path);
+        assert_eq!(std::fs::read(&victim).unwrap(), b"victim");
+        assert!(std::fs::symlink_metadata(&path)
+            .unwrap()
+            .file_type()
+            .is_file());
+        assert_eq!(std::fs::read(&path).unwrap(), b"png");
+
+        // a stale file with other content is rewritten too
+        std::fs::write(&path, b"gif").unwrap();
+        write_icon(&icons, "icon.png", b"png").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"png");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ignores_unexpected_signatures() {
+        sent(5);
+        assert!(signal("NotificationClosed", 5, "dismissed".to_variant()).is_none());
+        assert!(signal("ActionInvoked", 5, 1u32.to_variant()).is_none());
+        assert!(signal_event("NotificationClosed", &tuple(&[5u32.to_variant()])).is_none());
+        assert!(signal("CapabilitiesChanged", 5, 1u32.to_variant()).is_none());
+    }
+}
@@ -0,0 +1,806 @@
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::{io::Read, ops::Range, path::Path};
+
+#[cfg(target_os = "linux")]
+use crate::event::Event;
+#[cfg(target_os = "linux")]
+use glib::{translate::ToGlibPtr, Cast};
+#[cfg(target_os = "linux")]
+use gtk::WidgetExt;
+#[cfg(target_os = "linux")]
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    os::raw::c_uint,
+    ptr,
+    rc::Rc,
+};
+#[cfg(target_os = "linux")]
+use std::{ffi::CStr, os::raw::c_char};
+#[cfg(target_os = "linux")]
+use webkit2gtk::{
+    SecurityManagerExt, URISchemeRequest, URISchemeRequestExt, WebContextExt, WebViewExt,
+};
+
+#[cfg(target_os = "linux")]
+thread_local! {
+  static NEXT_REQUEST_ID: RefCell<u64> = RefCell::new(0);
+  // requests waiting for an answer from the backend script
+  static PENDING_REQUESTS: RefCell<HashMap<u64, URISchemeRequest>> = RefCell::new(HashMap::new());
+  // webkit keeps a single handler per scheme and context, ours dispatch by view
+  static REGISTERED_SCHEMES: RefCell<HashSet<(webkit2gtk::WebContext, String)>> = RefCell::new(HashSet::new());
+  static SCHEME_HANDLERS: RefCell<HashMap<String, HashMap<webkit2gtk::WebView, SchemeHandler>>> = RefCell::new(HashMap::new());
+}
+
+#[cfg(target_os = "linux")]
+type SchemeHandler = Rc<dyn Fn(&URISchemeRequest)>;
+
+/// HTTP-like request received by our protocol handlers, wry doesn't
+/// expose the request headers so they are only filled on Linux.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub uri: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn new(uri: &str) -> Self {
+        Self {
+            uri: uri.into(),
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub enum Body {
+    Bytes(Vec<u8>),
+    /// read lazily, used to stream large assets
+    Reader(Box<dyn Read + Send>, u64),
+}
+
+impl Body {
+    /// length in bytes, like the size of an asset
+    #[cfg(target_os = "linux")]
+    pub fn size(&self) -> u64 {
+        match self {
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::Reader(_, length) => *length,
+        }
+    }
+
+    pub fn into_bytes(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Body::Bytes(bytes) => Ok(bytes),
+            Body::Reader(mut reader, length) => {
+                let mut bytes = Vec::with_capacity(length as usize);
+                reader.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+/// HTTP-like response returned by our protocol handlers.
+///
+/// WebKitGTK delivers the status and headers. On other platforms wry only
+/// takes the body: every response is a 200 and the MIME type is sniffed
+/// from the content and the url.
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+}
+
+impl Response {
+    pub fn new(status: u16, mime_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), mime_type.into())],
+            body: Body::Bytes(body),
+        }
+    }
+
+    /// Stream a seekable asset of the given size, honoring the `Range`
+    /// header of the request. webkit reads it as the page consumes it, wry
+    /// only takes bytes so it's read in memory there.
+    pub fn ranged<F>(request: &Request, mime_type: &str, size: u64, open: F) -> Option<Self>
+    where
+        F: FnOnce(Range<u64>) -> Option<Box<dyn Read + Send>>,
+    {
+        let range = request
+            .header("Range")
+            .and_then(|header| parse_range(header, size));
+
+        let response = match range {
+            Some(range) if range.start >= range.end => {
+                return Some(
+                    Self::new(416, "text/plain", Vec::new())
+                        .with_header("Content-Range", &format!("bytes */{}", size)),
+                )
+            }
+            Some(range) => Self {
+                status: 206,
+                headers: vec![
+                    ("Content-Type".into(), mime_type.into()),
+                    (
+                        "Content-Range".into(),
+                        format!("bytes {}-{}/{}", range.start, range.end - 1, size),
+                    ),
+                ],
+                body: Body::Reader(open(range.clone())?, range.end - range.start),
+            },
+            None => Self {
+                status: 200,
+                headers: vec![("Content-Type".into(), mime_type.into())],
+                body: Body::Reader(open(0..size)?, size),
+            },
+        };
+
+        Some(response.with_header("Accept-Ranges", "bytes"))
+    }
+
+    /// 404 response, using the `404.html` page of the app when there is one.
+    pub fn not_found(page: Option<Vec<u8>>) -> Self {
+        match page {
+            Some(page) => Self::new(404, "text/html", page),
+            None => Self::new(404, "text/plain", b"Not Found".to_vec()),
+        }
+    }
+
+    pub fn forbidden() -> Self {
+        Self::new(403, "text/plain", b"Forbidden".to_vec())
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn mime_type(&self) -> &str {
+        self.header("Content-Type")
+            .unwrap_or("application/octet-stream")
+    }
+}
+
+/// Parse a single `bytes=` range, `None` when the header is invalid and the
+/// whole asset should be served, an empty range when it can't be satisfied.
+pub fn parse_range(header: &str, size: u64) -> Option<Range<u64>> {
+    let range = header.trim().strip_prefix("bytes=")?;
+    // multiple ranges are not supported, serve the whole asset instead
+    if range.contains(',') {
+        return None;
+    }
+
+    let (start, end) = range.split_at(range.find('-')?);
+    let end = &end[1..];
+    let range = match (start.trim(), end.trim()) {
+        ("", "") => return None,
+        // last `suffix` bytes
+        ("", suffix) => size.saturating_sub(suffix.parse().ok()?)..size,
+        (start, "") => start.parse().ok()?..size,
+        (start, end) => {
+            let start: u64 = start.parse().ok()?;
+            let end: u64 = end.parse().ok()?;
+            if end < start {
+                return None;
+            }
+            start..(end + 1).min(size)
+        }
+    };
+
+    if range.start >= size {
+        return Some(size..size);
+    }
+    Some(range)
+}
+
+/// How request paths are mapped to assets.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetOptions {
+    /// File served for directory urls, `dir/` serves `dir/index.html`.
+    #[serde(default = "default_index")]
+    pub index: String,
+    /// File served for unknown paths without extension, used by single-page
+    /// apps doing client-side routing.
+    #[serde(default)]
+    pub fallback: Option<String>,
+}
+
+fn default_index() -> String {
+    "index.html".into()
+}
+
+impl Default for AssetOptions {
+    fn default() -> Self {
+        Self {
+            index: default_index(),
+            fallback: None,
+        }
+    }
+}
+
+/// Find the asset to serve for the given path.
+pub fn resolve_asset<F>(path: &str, options: &AssetOptions, exists: F) -> Option<String>
+where
+    F: Fn(&str) -> bool,
+{
+    let index = if path.is_empty() {
+        options.index.clone()
+    } else {
+        format!("{}/{}", path, options.index)
+    };
+
+    let mut candidates = vec![path.to_string(), index];
+    if Path::new(path).extension().is_none() {
+        candidates.extend(options.fallback.clone());
+    }
+
+    candidates
+        .into_iter()
+        .filter(|candidate| !candidate.is_empty())
+        .find(|candidate| exists(candidate))
+}
+
+/// Resolve a requested url to an asset path relative to the asset root.
+///
+/// `.` and `..` segments are resolved, `None` is returned when the path
+/// would escape the root.
+pub fn asset_path(uri: &str, root_file_name: &str) -> Option<String> {
+    let path = uri.trim_start_matches("wry://");
+    let path = path
+        .split(|c| c == '?' || c == '#')
+        .next()
+        .unwrap_or_default();
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    let path = path.trim_start_matches("./");
+    let path = path
+        .strip_prefix(&format!("{}/", root_file_name))
+        .unwrap_or(path);
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            // windows separators, drive letters and NUL are never part of an asset path
+            segment if segment.contains(|c| c == '\\' || c == ':' || c == '\0') => return None,
+            segment => segments.push(segment),
+        }
+    }
+
+    Some(segments.join("/"))
+}
+
+/// Guess the MIME type of an asset from its extension.
+pub fn mime_type(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html",
+        "js" | "mjs" | "cjs" => "text/javascript",
+        "css" => "text/css",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "wasm" => "application/wasm",
+        "xml" => "application/xml",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" | "oga" => "audio/ogg",
+        "flac" => "audio/flac",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Register a protocol on the webkit context so we control the MIME type
+/// of every response instead of letting wry sniff the content.
+#[cfg(target_os = "linux")]
+pub fn register<F>(view: &webkit2gtk::WebView, scheme: &str, handler: F)
+where
+    F: Fn(&Request) -> crate::Result<Response> + 'static,
+{
+    register_scheme(view, scheme, move |request| {
+        let uri = request
+            .get_uri()
+            .map(|uri| uri.to_string())
+            .unwrap_or_default();
+        let response = handler(&Request {
+            headers: request_headers(request),
+            ..Request::new(&uri)
+        });
+        finish(request, response);
+    });
+}
+
+/// Register a protocol answered by the backend script, requests are
+/// pushed as `protocolRequest` events and answered with [`respond`].
+#[cfg(target_os = "linux")]
+pub fn register_backend(id: u64, view: &webkit2gtk::WebView, scheme: &str) {
+    let scheme_name = scheme.to_string();
+    register_scheme(view, scheme, move |request| {
+        let request_id = NEXT_REQUEST_ID.with(|cell| cell.replace_with(|&mut i| i + 1));
+        let url = request
+            .get_uri()
+            .map(|uri| uri.to_string())
+            .unwrap_or_default();
+        let path = request
+            .get_path()
+            .map(|path| path.to_string())
+            .unwrap_or_default();
+        PENDING_REQUESTS.with(|cell| cell.borrow_mut().insert(request_id, request.clone()));
+        crate::push_event(
+            id,
+            Event::ProtocolRequest {
+                request_id,
+                scheme: scheme_name.clone(),
+                url,
+                path,
+            },
+        );
+    });
+}
+
+/// Answer a request made to a protocol registered with [`register_backend`],
+/// the MIME type is guessed from the path when there is no `Content-Type`.
+#[cfg(target_os = "linux")]
+pub fn respond(request_id: u64, mut response: Response) -> crate::Result<()> {
+    let request = PENDING_REQUESTS
+        .with(|cell| cell.borrow_mut().remove(&request_id))
+        .ok_or_else(|| {
+            anyhow::anyhow!("Could not find protocol request with id: {}", request_id)
+        })?;
+
+    if response.header("Content-Type").is_none() {
+        let path = request
+            .get_path()
+            .map(|path| path.to_string())
+            .unwrap_or_default();
+        response = response.with_header("Content-Type", mime_type(&path));
+    }
+
+    finish(&request, Ok(response));
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn respond(request_id: u64, _response: Response) -> crate::Result<()> {
+    Err(anyhow::anyhow!(
+        "Could not find protocol request with id: {}",
+        request_id
+    ))
+}
+
+/// Schemes handled by the webview itself that can't be overridden.
+pub fn is_reserved(scheme: &str) -> bool {
+    matches!(
+        scheme.to_lowercase().as_str(),
+        "wry" | "http" | "https" | "file" | "about" | "data" | "blob" | "javascript" | "ws" | "wss"
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn register_scheme<F>(view: &webkit2gtk::WebView, scheme: &str, handler: F)
+where
+    F: Fn(&URISchemeRequest) + 'static,
+{
+    let context = match view.get_context() {
+        Some(context) => context,
+        None => return,
+    };
+
+    SCHEME_HANDLERS.with(|cell| {
+        cell.borrow_mut()
+            .entry(scheme.to_string())
+            .or_default()
+            .insert(view.clone(), Rc::new(handler));
+    });
+    let scheme_name = scheme.to_string();
+    view.connect_destroy(move |view| {
+        SCHEME_HANDLERS.with(|cell| {
+            if let Some(handlers) = cell.borrow_mut().get_mut(&scheme_name) {
+                handlers.remove(view);
+            }
+        });
+    });
+
+    let registered = REGISTERED_SCHEMES.with(|cell| {
+        !cell
+            .borrow_mut()
+            .insert((context.clone(), scheme.to_string()))
+    });
+    if registered {
+        return;
+    }
+
+    // allow fetch, ES modules and secure context apis on our scheme
+    if let Some(security_manager) = context.get_security_manager() {
+        security_manager.register_uri_scheme_as_secure(scheme);
+        security_manager.register_uri_scheme_as_cors_enabled(scheme);
+    }
+
+    let scheme_name = scheme.to_string();
+    context.register_uri_scheme(scheme, move |request: &URISchemeRequest| {
+        let handler = request.get_web_view().and_then(|view| {
+            SCHEME_HANDLERS.with(|cell| {
+                cell.borrow()
+                    .get(&scheme_name)
+                    .and_then(|handlers| handlers.get(&view))
+                    .cloned()
+            })
+        });
+        match handler {
+            Some(handler) => handler(request),
+            None => finish(
+                request,
+                Err(anyhow::anyhow!("No {} handler for this view", scheme_name)),
+            ),
+        }
+    });
+}
+
+/// Headers sent by the page, media elements ask for ranges.
+#[cfg(target_os = "linux")]
+fn request_headers(request: &URISchemeRequest) -> Vec<(String, String)> {
+    unsafe extern "C" fn collect(
+        name: *const c_char,
+        value: *const c_char,
+        headers: glib_sys::gpointer,
+    ) {
+        let headers = &mut *(headers as *mut Vec<(String, String)>);
+        headers.push((
+            CStr::from_ptr(name).to_string_lossy().into_owned(),
+            CStr::from_ptr(value).to_string_lossy().into_owned(),
+        ));
+    }
+
+    let mut headers = Vec::new();
+    unsafe {
+        let soup_headers =
+            ffi::webkit_uri_scheme_request_get_http_headers(request.to_glib_none().0);
+        if !soup_headers.is_null() {
+            soup_sys::soup_message_headers_foreach(
+                soup_headers,
+                Some(collect),
+                &mut headers as *mut Vec<(String, String)> as glib_sys::gpointer,
+            );
+        }
+    }
+    headers
+}
+
+/// Errors are reported as a failed load.
+#[cfg(target_os = "linux")]
+fn finish(request: &URISchemeRequest, response: crate::Result<Response>) {
+    let uri = request
+        .get_uri()
+        .map(|uri| uri.to_string())
+        .unwrap_or_default();
+    match response {
+        Ok(response) => {
+            let length = response.body.size() as i64;
+            let mime_type = response.mime_type().to_string();
+            let stream: gio::InputStream = match response.body {
+                Body::Bytes(bytes) => {
+                    let bytes = glib::Bytes::from_owned(bytes);
+                    gio::MemoryInputStream::from_bytes(&bytes).upcast()
+                }
+                Body::Reader(reader, _) => gio::ReadInputStream::new(reader).upcast(),
+            };
+
+            unsafe {
+                let scheme_response =
+                    ffi::webkit_uri_scheme_response_new(stream.to_glib_none().0, length);
+                ffi::webkit_uri_scheme_response_set_status(
+                    scheme_response,
+                    response.status as c_uint,
+                    ptr::null(),
+                );
+                ffi::webkit_uri_scheme_response_set_content_type(
+                    scheme_response,
+                    mime_type.to_glib_none().0,
+                );
+                let headers =
+                    soup_sys::soup_message_headers_new(soup_sys::SOUP_MESSAGE_HEADERS_RESPONSE);
+                for (name, value) in &response.headers {
+                    if !name.eq_ignore_ascii_case("Content-Type") {
+                        soup_sys::soup_message_headers_append(
+                            headers,
+                            name.to_glib_none().0,
+                            value.to_glib_none().0,
+                        );
+                    }
+                }
+                // the response takes ownership of the headers
+                ffi::webkit_uri_scheme_response_set_http_headers(scheme_response, headers);
+                ffi::webkit_uri_scheme_request_finish_with_response(
+                    request.to_glib_none().0,
+                    scheme_response,
+                );
+                glib::gobject_sys::g_object_unref(scheme_response as *mut _);
+            }
+        }
+        Err(err) => {
+            let mut error =
+                glib::Error::new(gio::IOErrorEnum::Failed, &format!("{}: {}", uri, err));
+            request.finish_error(&mut error);
+        }
+    }
+}
+
+/// Custom scheme request headers and responses with a status and headers,
+/// added in WebKitGTK 2.36 which webkit2gtk-sys doesn't bind yet. The symbols come with the
+/// webkit2gtk library it links.
+#[cfg(target_os = "linux")]
+mod ffi {
+    use soup_sys::SoupMessageHeaders;
+    use std::os::raw::{c_char, c_uint};
+    use webkit2gtk_sys::WebKitURISchemeRequest;
+
+    #[repr(C)]
+    pub struct WebKitURISchemeResponse {
+        _private: [u8; 0],
+    }
+
+    extern "C" {
+        pub fn webkit_uri_scheme_request_get_http_headers(
+            request: *mut WebKitURISchemeRequest,
+        ) -> *mut SoupMessageHeaders;
+        pub fn webkit_uri_scheme_response_new(
+            stream: *mut gio_sys::GInputStream,
+            stream_length: i64,
+        ) -> *mut WebKitURISchemeResponse;
+        pub fn webkit_uri_scheme_response_set_status(
+            response: *mut WebKitURISchemeResponse,
+            status_code: c_uint,
+            reason_phrase: *const c_char,
+        );
+        pub fn webkit_uri_scheme_response_set_content_type(
+            response: *mut WebKitURISchemeResponse,
+            content_type: *const c_char,
+        );
+        pub fn webkit_uri_scheme_response_set_http_headers(
+            response: *mut WebKitURISchemeResponse,
+            headers: *mut SoupMessageHeaders,
+        );
+        pub fn webkit_uri_scheme_request_finish_with_response(
+            request: *mut WebKitURISchemeRequest,
+            response: *mut WebKitURISchemeResponse,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asset_path_resolves_dot_segments() {
+        assert_eq!(
+            asset_path("wry://css/../app.css", "index.js"),
+            Some("app.css".into())
+        );
+        assert_eq!(
+            asset_path("wry://./css/./app.css", "index.js"),
+            Some("css/app.css".into())
+        );
+        assert_eq!(asset_path("wry://..", "index.js"), None);
+        assert_eq!(asset_path("wry://css/../../secret", "index.js"), None);
+    }
+
+    #[test]
+    fn asset_path_decodes_before_resolving() {
+        assert_eq!(asset_path("wry://%2e%2e/secret", "index.js"), None);
+        assert_eq!(
+            asset_path("wry://css/%2E%2E/%2e%2e/secret", "index.js"),
+            None
+        );
+        assert_eq!(asset_path("wry://..%2fsecret", "index.js"), None);
+        assert_eq!(
+            asset_path("wry://my%20file.txt", "index.js"),
+            Some("my file.txt".into())
+        );
+        assert_eq!(asset_path("wry://%ff", "index.js"), None);
+    }
+
+    #[test]
+    fn asset_path_stays_relative_to_the_root() {
+        assert_eq!(
+            asset_path("wry:///etc/passwd", "index.js"),
+            Some("etc/passwd".into())
+        );
+        assert_eq!(asset_path("wry://C:/Windows/win.ini", "index.js"), None);
+        assert_eq!(asset_path("wry://..\\secret", "index.js"), None);
+        assert_eq!(asset_path("wry://%5c%5cserver/share", "index.js"), None);
+        assert_eq!(asset_path("wry://app.css%00.png", "index.js"), None);
+    }
+
+    #[test]
+    fn asset_path_strips_query_and_fragment() {
+        assert_eq!(
+            asset_path("wry://app.css?v=1", "index.js"),
+            Some("app.css".into())
+        );
+        assert_eq!(
+            asset_path("wry://page.html#top", "index.js"),
+            Some("page.html".into())
+        );
+        assert_eq!(
+            asset_path("wry://a.js?next=../../b#c", "index.js"),
+            Some("a.js".into())
+        );
+        assert_eq!(asset_path("wry://?x", "index.js"), Some("".into()));
+    }
+
+    #[test]
+    fn asset_path_strips_the_root_file_name() {
+        assert_eq!(
+            asset_path("wry://index.js/app.css", "index.js"),
+            Some("app.css".into())
+        );
+        assert_eq!(
+            asset_path("wry://./index.js/css/app.css", "index.js"),
+            Some("css/app.css".into())
+        );
+        assert_eq!(asset_path("wry://index.js/../../secret", "index.js"), None);
+        // only a whole segment is stripped
+        assert_eq!(
+            asset_path("wry://index.json", "index.js"),
+            Some("index.json".into())
+        );
+        assert_eq!(
+            asset_path("assets/logo.png", ""),
+            Some("assets/logo.png".into())
+        );
+    }
+
+    #[test]
+    fn parse_range_reads_single_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(0..100));
+        assert_eq!(parse_range("bytes=500-", 1000), Some(500..1000));
+        assert_eq!(parse_range("bytes=-200", 1000), Some(800..1000));
+        // clamped to the size
+        assert_eq!(parse_range("bytes=900-2000", 1000), Some(900..1000));
+        assert_eq!(parse_range("bytes=-2000", 1000), Some(0..1000));
+    }
+
+    #[test]
+    fn parse_range_serves_everything_for_invalid_headers() {
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), None);
+        assert_eq!(parse_range("bytes=-", 1000), None);
+        assert_eq!(parse_range("bytes=9-5", 1000), None);
+        assert_eq!(parse_range("items=0-5", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+    }
+
+    #[test]
+    fn parse_range_flags_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(1000..1000));
+        assert_eq!(parse_range("bytes=2000-3000", 1000), Some(1000..1000));
+    }
+
+    fn ranged(range: Option<&str>) -> Response {
+        let mut request = Request::new("wry://video.mp4");
+        if let Some(range) = range {
+            request.headers.push(("range".into(), range.into()));
+        }
+        Response::ranged(&request, "video/mp4", 1000, |range| {
+            let bytes = vec![0u8; (range.end - range.start) as usize];
+            Some(Box::new(std::io::Cursor::new(bytes)) as Box<dyn Read + Send>)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn ranged_responses_serve_the_requested_bytes() {
+        let response = ranged(Some("bytes=100-199"));
+        assert_eq!(response.status, 206);
+        assert_eq!(response.header("Content-Range"), Some("bytes 100-199/1000"));
+        assert_eq!(response.header("Accept-Ranges"), Some("bytes"));
+        assert_eq!(response.mime_type(), "video/mp4");
+        assert_eq!(response.body.into_bytes().unwrap().len(), 100);
+
+        let response = ranged(None);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("Content-Range"), None);
+        assert_eq!(response.header("Accept-Ranges"), Some("bytes"));
+        assert_eq!(response.body.into_bytes().unwrap().len(), 1000);
+
+        let response = ranged(Some("bytes=5000-"));
+        assert_eq!(response.status, 416);
+        assert_eq!(response.header("Content-Range"), Some("bytes */1000"));
+        assert_eq!(response.body.into_bytes().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn errors_carry_their_status() {
+        let response = Response::not_found(None);
+        assert_eq!(response.status, 404);
+        assert_eq!(response.mime_type(), "text/plain");
+        let response = Response::not_found(Some(b"<h1>Gone</h1>".to_vec()));
+        assert_eq!(response.status, 404);
+        assert_eq!(response.mime_type(), "text/html");
+        assert_eq!(Response::forbidden().status, 403);
+    }
+
+    #[test]
+    fn response_headers_ignore_case() {
+        let response =
+            Response::new(200, "text/css", Vec::new()).with_header("Cache-Control", "no-cache");
+        assert_eq!(response.header("cache-control"), Some("no-cache"));
+        assert_eq!(response.header("content-type"), Some("text/css"));
+        assert_eq!(response.header("ETag"), None);
+    }
+
+    #[test]
+    fn resolve_asset_serves_the_index_of_directories() {
+        let options = AssetOptions::default();
+        let exists = |path: &str| matches!(path, "index.html" | "docs/index.html" | "app.css");
+        assert_eq!(
+            resolve_asset("", &options, exists),
+            Some("index.html".into())
+        );
+        assert_eq!(
+            resolve_asset("docs", &options, exists),
+            Some("docs/index.html".into())
+        );
+        assert_eq!(
+            resolve_asset("app.css", &options, exists),
+            Some("app.css".into())
+        );
+        assert_eq!(resolve_asset("missing", &options, exists), None);
+    }
+
+    #[test]
+    fn resolve_asset_falls_back_for_routes_only() {
+        let options = AssetOptions {
+            index: "index.html".into(),
+            fallback: Some("index.html".into()),
+        };
+        let exists = |path: &str| path == "index.html";
+        assert_eq!(
+            resolve_asset("users/42", &options, exists),
+            Some("index.html".into())
+        );
+        assert_eq!(resolve_asset("missing.png", &options, exists), None);
+    }
+}
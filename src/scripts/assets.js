@@ -0,0 +1,18 @@
+globalThis.Wry = globalThis.Wry ?? {};
+
+// files of the app, read from the bundle in a compiled binary
+Wry.assets = {
+   read(path) {
+      // an empty asset comes back as its length
+      const bytes = Deno.core.binOpSync('wry_assets_read', 0, new TextEncoder().encode(path));
+      return bytes instanceof Uint8Array ? bytes : new Uint8Array(0);
+   },
+
+   readText(path) {
+      return new TextDecoder().decode(this.read(path));
+   },
+
+   list() {
+      return Deno.core.jsonOpSync('wry_assets_list', {});
+   },
+};
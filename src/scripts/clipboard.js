@@ -0,0 +1,33 @@
+globalThis.Wry = globalThis.Wry ?? {};
+
+// system clipboard, reads resolve to null when there is nothing to paste
+Wry.clipboard = {
+   readText() {
+      return Deno.core.jsonOpSync('wry_clipboard_read', { format: 'text' });
+   },
+
+   writeText(text) {
+      Deno.core.jsonOpSync('wry_clipboard_write', { format: 'text', text });
+   },
+
+   readHtml() {
+      return Deno.core.jsonOpSync('wry_clipboard_read', { format: 'html' });
+   },
+
+   // text is pasted by apps which don't understand html
+   writeHtml(html, text = "") {
+      Deno.core.jsonOpSync('wry_clipboard_write', { format: 'html', html, text });
+   },
+
+   // PNG bytes
+   readImage() {
+      // an empty result comes back as its length
+      const bytes = Deno.core.binOpSync('wry_clipboard_read_image');
+      return bytes instanceof Uint8Array ? bytes : null;
+   },
+
+   writeImage(bytes) {
+      const buffer = bytes instanceof Uint8Array ? bytes : new Uint8Array(bytes);
+      Deno.core.jsonOpSync('wry_clipboard_write', { format: 'image' }, buffer);
+   },
+};
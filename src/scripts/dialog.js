@@ -0,0 +1,43 @@
+globalThis.Wry = globalThis.Wry ?? {};
+
+// native dialogs, modal to the window of the given webview
+Wry.dialog = {
+   open(webview, options = {}) {
+      return this.file(webview, 'open', options);
+   },
+
+   save(webview, options = {}) {
+      return this.file(webview, 'save', options);
+   },
+
+   pickFolder(webview, options = {}) {
+      return this.file(webview, 'pickFolder', options);
+   },
+
+   // resolves with the index of the clicked button, null when closed
+   async message(webview, options = {}) {
+      const { button } = await this.show(webview, { ...options, input: undefined });
+      return button;
+   },
+
+   // resolves with the entered text, null when cancelled
+   async prompt(webview, { defaultValue = "", ...options } = {}) {
+      const { button, text } = await this.show(webview, {
+         level: 'question',
+         buttons: ['_Cancel', '_OK'],
+         ...options,
+         input: defaultValue,
+      });
+      return button === 1 ? text : null;
+   },
+
+   show(webview, options) {
+      const requestId = Deno.core.jsonOpSync('wry_dialog_message', { ...options, id: webview.id });
+      return webview.waitForDialog(requestId);
+   },
+
+   file(webview, kind, options) {
+      const requestId = Deno.core.jsonOpSync('wry_dialog_file', { ...options, id: webview.id, kind });
+      return webview.waitForDialog(requestId);
+   },
+};
@@ -0,0 +1,16 @@
+// the init script is evaluated at the start of every page load
+____rpcPageEvent("navigationStarted", { url: window.location.href });
+window.addEventListener("load", function () {
+   ____rpcPageEvent("navigationFinished", { url: window.location.href });
+});
+
+window.addEventListener("DOMContentLoaded", function () {
+   let title = document.title;
+   ____rpcPageEvent("titleChanged", { title });
+   new MutationObserver(function () {
+      if (document.title !== title) {
+         title = document.title;
+         ____rpcPageEvent("titleChanged", { title });
+      }
+   }).observe(document.head || document.documentElement, { subtree: true, childList: true, characterData: true });
+});
@@ -0,0 +1,32 @@
+function ____wryIsAllowed(href) {
+   const url = new URL(href, window.location.href);
+   if (["wry:", "about:"].includes(url.protocol)) return true;
+   return window.____wryAllowedOrigins.some(function (allowed) {
+      allowed = allowed.replace(/\/+$/, "");
+      if (allowed.endsWith(":")) return url.protocol === allowed;
+      try { return new URL(allowed).origin === url.origin; } catch (_) { return false; }
+   });
+};
+
+// intercept clicked links and `window.open`, we can't hook into the native
+// navigation on this platform so assigning `location.href` isn't covered
+document.addEventListener("click", function (event) {
+   const link = event.target.closest && event.target.closest("a[href]");
+   if (!link || event.defaultPrevented) return;
+   const url = new URL(link.getAttribute("href"), window.location.href).href;
+   if (!____wryIsAllowed(url)) {
+      event.preventDefault();
+      // only links of the main frame are opened in the system browser
+      rpc.call("navigationBlocked", { url, external: window === window.top });
+   }
+}, true);
+
+const ____wryOpen = window.open;
+window.open = function (href, ...args) {
+   if (href === undefined || ____wryIsAllowed(String(href))) {
+      return ____wryOpen.call(window, href, ...args);
+   }
+   const url = new URL(String(href), window.location.href).href;
+   rpc.call("navigationBlocked", { url, external: false });
+   return null;
+};
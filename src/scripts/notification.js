@@ -0,0 +1,40 @@
+globalThis.Wry = globalThis.Wry ?? {};
+
+// desktop notifications, onClick gets the id of the clicked action or null
+// for the notification itself, onClose the reason it was closed
+Wry.notify = function ({ onClick, onClose, ...options } = {}) {
+   const id = Deno.core.jsonOpSync('wry_notify', options);
+   Wry.notify.handlers.set(id, { onClick, onClose });
+   Wry.notify.poll();
+   return id;
+};
+
+Wry.notify.handlers = new Map();
+
+Wry.notify.close = function (id) {
+   Deno.core.jsonOpSync('wry_notification_close', { id });
+};
+
+// events are pulled while notifications are shown
+Wry.notify.poll = function (delta = 1000/30) {
+   if (this.interval !== undefined) {
+      return;
+   }
+
+   this.interval = setInterval(() => {
+      for (const event of Deno.core.jsonOpSync('wry_notification_events', {})) {
+         const handlers = this.handlers.get(event.notificationId);
+         if (event.event === 'notificationClicked') {
+            handlers?.onClick?.(event.action);
+         } else if (event.event === 'notificationClosed') {
+            this.handlers.delete(event.notificationId);
+            handlers?.onClose?.(event.reason);
+         }
+      }
+
+      if (this.handlers.size === 0) {
+         clearInterval(this.interval);
+         this.interval = undefined;
+      }
+   }, delta);
+};
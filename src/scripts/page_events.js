@@ -0,0 +1,19 @@
+function ____rpcPageEvent(name, params) {
+   rpc.call(name, params === undefined ? null : params);
+};
+
+window.addEventListener("load", function () { ____rpcPageEvent("load"); });
+window.addEventListener("beforeunload", function () { ____rpcPageEvent("beforeUnload"); });
+
+// forward console messages to the backend, while keeping the page console working
+["log", "info", "warn", "error", "debug"].forEach(function (level) {
+   const original = console[level];
+   console[level] = function (...args) {
+      const message = args.map(function (arg) {
+         if (typeof arg === "string") return arg;
+         try { return JSON.stringify(arg); } catch (_) { return String(arg); }
+      }).join(" ");
+      ____rpcPageEvent("consoleMessage", { level, message });
+      return original.apply(console, args);
+   };
+});
@@ -1,6 +1,108 @@
 class Webview {
-   constructor(url) {
-      this.id = Deno.core.jsonOpSync('wry_new', { url });
+   constructor(url, options = {}) {
+      const { protocols = {}, ...rest } = options;
+      this.protocols = protocols;
+      this.dialogs = new Map();
+      this.id = Deno.core.jsonOpSync('wry_new', { ...rest, url, protocols: Object.keys(protocols) });
+   }
+
+   async handleProtocolRequest({ requestId, scheme, url, path }) {
+      let response;
+      try {
+         response = await this.protocols[scheme]({ url, path });
+      } catch (error) {
+         response = { status: 500, headers: { "Content-Type": "text/plain" }, body: String(error) };
+      }
+      // the MIME type is guessed from the path without a Content-Type header
+      const { status = 200, headers = {}, body = "" } = response ?? {};
+      const bytes = typeof body === "string"
+         ? new TextEncoder().encode(body)
+         : body instanceof Uint8Array ? body : new Uint8Array(body);
+      Deno.core.jsonOpSync('wry_protocol_respond', { requestId, status, headers }, bytes);
+   }
+
+   // resolved when the matching dialogResponse event is pulled in `run`
+   waitForDialog(requestId) {
+      return new Promise((resolve) => this.dialogs.set(requestId, resolve));
+   }
+
+   resolveDialog({ requestId, result }) {
+      const resolve = this.dialogs.get(requestId);
+      this.dialogs.delete(requestId);
+      resolve?.(result);
+   }
+
+   navigate(url) {
+      Deno.core.jsonOpSync('wry_navigate', { id: this.id, url });
+   }
+
+   loadHtml(html) {
+      Deno.core.jsonOpSync('wry_load_html', { id: this.id, html });
+   }
+
+   reload() {
+      Deno.core.jsonOpSync('wry_reload', { id: this.id });
+   }
+
+   back() {
+      Deno.core.jsonOpSync('wry_go_back', { id: this.id });
+   }
+
+   forward() {
+      Deno.core.jsonOpSync('wry_go_forward', { id: this.id });
+   }
+
+   // clicks are delivered as menuItemClicked events
+   setMenu(items = []) {
+      Deno.core.jsonOpSync('wry_set_menu', { id: this.id, items });
+   }
+
+   setContextMenu(items = []) {
+      Deno.core.jsonOpSync('wry_set_context_menu', { id: this.id, items });
+   }
+
+   // the window is only closed when no handler called preventDefault or
+   // returned false, handlers may be async to ask the user first
+   async handleCloseRequested(event, callback) {
+      let prevented = false;
+      event.preventDefault = () => { prevented = true; };
+      const result = await callback?.(event);
+      if (!prevented && result !== false && !this.closed) {
+         this.close();
+      }
+   }
+
+   close() {
+      // hidden windows can be closed again
+      this.closed = Deno.core.jsonOpSync('wry_close', { id: this.id });
+   }
+
+   // pressed keys are delivered as shortcut events, global ones even when
+   // another app has the focus
+   registerShortcut(accelerator, { global = false } = {}) {
+      Deno.core.jsonOpSync('wry_register_shortcut', { id: this.id, accelerator, global });
+   }
+
+   unregisterShortcut(accelerator) {
+      Deno.core.jsonOpSync('wry_unregister_shortcut', { id: this.id, accelerator });
+   }
+
+   show() {
+      Deno.core.jsonOpSync('wry_set_visible', { id: this.id, visible: true });
+   }
+
+   hide() {
+      Deno.core.jsonOpSync('wry_set_visible', { id: this.id, visible: false });
+   }
+
+   // icon is the path of an app asset, clicks are delivered as trayClicked
+   // and menuItemClicked events
+   setTray({ icon, tooltip, menu = [] }) {
+      Deno.core.jsonOpSync('wry_set_tray', { id: this.id, icon, tooltip, menu });
+   }
+
+   removeTray() {
+      Deno.core.jsonOpSync('wry_remove_tray', { id: this.id });
    }
 
    loop() {
@@ -15,14 +117,22 @@ class Webview {
       callback,
       delta = 1000/30,
     ) {
+      // closeRequested is answered by handleCloseRequested
+      Deno.core.jsonOpSync('wry_intercept_close', { id: this.id });
       return new Promise((resolve) => {
         const interval = setInterval(() => {
           const success = this.loop();
   
-          if (callback !== undefined) {
-            const events = this.step();
-  
-            for (const event of events) {
+          const events = this.step();
+
+          for (const event of events) {
+            if (event.event === 'protocolRequest') {
+              this.handleProtocolRequest(event);
+            } else if (event.event === 'dialogResponse') {
+              this.resolveDialog(event);
+            } else if (event.event === 'closeRequested') {
+              this.handleCloseRequested(event, callback);
+            } else if (callback !== undefined) {
               callback(event);
             }
           }
@@ -0,0 +1,398 @@
+#[cfg(target_os = "linux")]
+use crate::{accelerator::Accelerator, event::Event};
+#[cfg(target_os = "linux")]
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::CString,
+    os::raw::{c_int, c_uint},
+    ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+#[cfg(target_os = "linux")]
+use x11_dl::xlib;
+
+#[cfg(target_os = "linux")]
+thread_local! {
+  // (accelerator, keyval, modifiers) of each webview window
+  static WINDOW_SHORTCUTS: RefCell<HashMap<u64, Vec<(String, u32, gdk::ModifierType)>>> = RefCell::new(HashMap::new());
+  // opened with the first global shortcut
+  static X11: RefCell<Option<GlobalShortcuts>> = RefCell::new(None);
+}
+
+/// Caps lock and num lock must not prevent global shortcuts.
+#[cfg(target_os = "linux")]
+const IGNORED_MASKS: [c_uint; 4] = [
+    0,
+    xlib::LockMask,
+    xlib::Mod2Mask,
+    xlib::LockMask | xlib::Mod2Mask,
+];
+
+/// Set by `grab_error_handler` when a grab was refused.
+#[cfg(target_os = "linux")]
+static GRAB_FAILED: AtomicBool = AtomicBool::new(false);
+
+// a combination owned by another app is refused with an asynchronous
+// BadAccess, the default handler would exit the process
+#[cfg(target_os = "linux")]
+unsafe extern "C" fn grab_error_handler(
+    _display: *mut xlib::Display,
+    event: *mut xlib::XErrorEvent,
+) -> c_int {
+    if (*event).error_code == xlib::BadAccess {
+        GRAB_FAILED.store(true, Ordering::SeqCst);
+    }
+    0
+}
+
+#[cfg(target_os = "linux")]
+struct Grab {
+    id: u64,
+    accelerator: String,
+    keycode: c_uint,
+    modifiers: c_uint,
+}
+
+/// Keys grabbed on the root window of the X server, they are reported
+/// whichever window has the focus.
+#[cfg(target_os = "linux")]
+struct GlobalShortcuts {
+    xlib: xlib::Xlib,
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    grabs: Vec<Grab>,
+}
+
+#[cfg(target_os = "linux")]
+impl GlobalShortcuts {
+    fn open() -> crate::Result<Self> {
+        let xlib = xlib::Xlib::open()?;
+        let display = unsafe { (xlib.XOpenDisplay)(ptr::null()) };
+        if display.is_null() {
+            return Err(anyhow::anyhow!(
+                "Global shortcuts need an X11 display, DISPLAY isn't set"
+            ));
+        }
+        let root = unsafe { (xlib.XDefaultRootWindow)(display) };
+        Ok(Self {
+            xlib,
+            display,
+            root,
+            grabs: Vec::new(),
+        })
+    }
+
+    fn grab(&mut self, id: u64, accelerator: &str, parsed: &Accelerator) -> crate::Result<()> {
+        let key = CString::new(parsed.key.as_str())?;
+        let keycode = unsafe {
+            let keysym = (self.xlib.XStringToKeysym)(key.as_ptr());
+            (self.xlib.XKeysymToKeycode)(self.display, keysym)
+        } as c_uint;
+        if keycode == 0 {
+            return Err(anyhow::anyhow!(
+                "Key of {} isn't on the keyboard",
+                accelerator
+            ));
+        }
+
+        let mut modifiers = 0;
+        if parsed.modifiers.shift {
+            modifiers |= xlib::ShiftMask;
+        }
+        if parsed.modifiers.ctrl {
+            modifiers |= xlib::ControlMask;
+        }
+        if parsed.modifiers.alt {
+            modifiers |= xlib::Mod1Mask;
+        }
+        if parsed.modifiers.meta {
+            modifiers |= xlib::Mod4Mask;
+        }
+
+        // another webview grabbed the same combination, the server already
+        // reports it to us
+        if !is_grabbed(&self.grabs, keycode, modifiers) && !self.grab_key(keycode, modifiers) {
+            return Err(anyhow::anyhow!(
+                "{} is already used by another application",
+                accelerator
+            ));
+        }
+
+        self.grabs.push(Grab {
+            id,
+            accelerator: accelerator.to_string(),
+            keycode,
+            modifiers,
+        });
+        Ok(())
+    }
+
+    /// Grab every variant of the combination, false when another app owns it.
+    fn grab_key(&self, keycode: c_uint, modifiers: c_uint) -> bool {
+        GRAB_FAILED.store(false, Ordering::SeqCst);
+        unsafe {
+            let previous = (self.xlib.XSetErrorHandler)(Some(grab_error_handler));
+            for mask in IGNORED_MASKS.iter() {
+                (self.xlib.XGrabKey)(
+                    self.display,
+                    keycode as i32,
+                    modifiers | mask,
+                    self.root,
+                    xlib::False,
+                    xlib::GrabModeAsync,
+                    xlib::GrabModeAsync,
+                );
+            }
+            // wait for the server to answer every grab
+            (self.xlib.XSync)(self.display, xlib::False);
+            let failed = GRAB_FAILED.load(Ordering::SeqCst);
+            if failed {
+                // release the variants we got, ungrabbing the others is a no-op
+                for mask in IGNORED_MASKS.iter() {
+                    (self.xlib.XUngrabKey)(
+                        self.display,
+                        keycode as i32,
+                        modifiers | mask,
+                        self.root,
+                    );
+                }
+                (self.xlib.XSync)(self.display, xlib::False);
+            }
+            (self.xlib.XSetErrorHandler)(previous);
+            !failed
+        }
+    }
+
+    fn ungrab(&mut self, keep: impl Fn(&Grab) -> bool) {
+        let (kept, removed): (Vec<Grab>, Vec<Grab>) = self.grabs.drain(..).partition(keep);
+        for (keycode, modifiers) in released(&kept, &removed) {
+            for mask in IGNORED_MASKS.iter() {
+                unsafe {
+                    (self.xlib.XUngrabKey)(
+                        self.display,
+                        keycode as i32,
+                        modifiers | mask,
+                        self.root,
+                    )
+                };
+            }
+        }
+        self.grabs = kept;
+        unsafe { (self.xlib.XFlush)(self.display) };
+    }
+
+    fn poll(&self) {
+        while unsafe { (self.xlib.XPending)(self.display) } > 0 {
+            let mut event: xlib::XEvent = unsafe { std::mem::zeroed() };
+            unsafe { (self.xlib.XNextEvent)(self.display, &mut event) };
+            if event.get_type() != xlib::KeyPress {
+                continue;
+            }
+
+            let key = unsafe { event.key };
+            let modifiers = key.state & !(xlib::LockMask | xlib::Mod2Mask);
+            for grab in &self.grabs {
+                if grab.keycode == key.keycode && grab.modifiers == modifiers {
+                    crate::push_event(
+                        grab.id,
+                        Event::Shortcut {
+                            accelerator: grab.accelerator.clone(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_grabbed(grabs: &[Grab], keycode: c_uint, modifiers: c_uint) -> bool {
+    grabs
+        .iter()
+        .any(|grab| grab.keycode == keycode && grab.modifiers == modifiers)
+}
+
+/// Key combinations to ungrab once `removed` are gone, the ones still
+/// registered by another webview stay grabbed.
+#[cfg(target_os = "linux")]
+fn released(kept: &[Grab], removed: &[Grab]) -> Vec<(c_uint, c_uint)> {
+    let mut released: Vec<(c_uint, c_uint)> = Vec::new();
+    for grab in removed {
+        let combination = (grab.keycode, grab.modifiers);
+        if !is_grabbed(kept, grab.keycode, grab.modifiers) && !released.contains(&combination) {
+            released.push(combination);
+        }
+    }
+    released
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for GlobalShortcuts {
+    fn drop(&mut self) {
+        self.ungrab(|_| false);
+        unsafe { (self.xlib.XCloseDisplay)(self.display) };
+    }
+}
+
+/// Report the accelerator with a `shortcut` event, global shortcuts are
+/// reported even when another app has the focus.
+#[cfg(target_os = "linux")]
+pub fn register(id: u64, accelerator: &str, global: bool) -> crate::Result<()> {
+    let parsed = accelerator.parse::<Accelerator>()?;
+    if is_registered(id, accelerator) {
+        return Err(anyhow::anyhow!("{} is already registered", accelerator));
+    }
+
+    if global {
+        X11.with(|cell| {
+            let mut x11 = cell.borrow_mut();
+            if x11.is_none() {
+                *x11 = Some(GlobalShortcuts::open()?);
+            }
+            x11.as_mut().unwrap().grab(id, accelerator, &parsed)
+        })
+    } else {
+        let (key, modifiers) = parsed.to_gtk();
+        WINDOW_SHORTCUTS.with(|cell| {
+            cell.borrow_mut().entry(id).or_default().push((
+                accelerator.to_string(),
+                gdk::keyval_to_lower(key),
+                modifiers,
+            ))
+        });
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn unregister(id: u64, accelerator: &str) {
+    WINDOW_SHORTCUTS.with(|cell| {
+        if let Some(shortcuts) = cell.borrow_mut().get_mut(&id) {
+            shortcuts.retain(|(registered, _, _)| registered != accelerator);
+        }
+    });
+    X11.with(|cell| {
+        if let Some(x11) = cell.borrow_mut().as_mut() {
+            x11.ungrab(|grab| grab.id != id || grab.accelerator != accelerator);
+        }
+    });
+}
+
+/// Unregister the shortcuts of a closed webview.
+#[cfg(target_os = "linux")]
+pub fn unregister_all(id: u64) {
+    WINDOW_SHORTCUTS.with(|cell| cell.borrow_mut().remove(&id));
+    X11.with(|cell| {
+        if let Some(x11) = cell.borrow_mut().as_mut() {
+            x11.ungrab(|grab| grab.id != id);
+        }
+    });
+}
+
+/// Match a key press of the webview window, before the page sees it.
+#[cfg(target_os = "linux")]
+pub fn handle_key_press(id: u64, event: &gdk::EventKey) -> bool {
+    // accelerators name the base level of their key, `Ctrl+Shift+1` and not `Ctrl+!`
+    let keyval = gdk::keyval_to_lower(crate::helpers::base_keyval(event));
+    let modifiers = event.get_state() & gtk::accelerator_get_default_mod_mask();
+
+    let accelerator = WINDOW_SHORTCUTS.with(|cell| {
+        cell.borrow().get(&id).and_then(|shortcuts| {
+            shortcuts
+                .iter()
+                .find(|(_, key, mods)| *key == keyval && *mods == modifiers)
+                .map(|(accelerator, _, _)| accelerator.clone())
+        })
+    });
+    match accelerator {
+        Some(accelerator) => {
+            crate::push_event(id, Event::Shortcut { accelerator });
+            true
+        }
+        None => false,
+    }
+}
+
+/// Dispatch the global shortcuts pressed since the last call.
+#[cfg(target_os = "linux")]
+pub fn poll_global() {
+    X11.with(|cell| {
+        if let Some(x11) = cell.borrow().as_ref() {
+            x11.poll();
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn is_registered(id: u64, accelerator: &str) -> bool {
+    let window = WINDOW_SHORTCUTS.with(|cell| {
+        cell.borrow().get(&id).map_or(false, |shortcuts| {
+            shortcuts
+                .iter()
+                .any(|(registered, _, _)| registered == accelerator)
+        })
+    });
+    let global = X11.with(|cell| {
+        cell.borrow().as_ref().map_or(false, |x11| {
+            x11.grabs
+                .iter()
+                .any(|grab| grab.id == id && grab.accelerator == accelerator)
+        })
+    });
+    window || global
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn register(_id: u64, _accelerator: &str, _global: bool) -> crate::Result<()> {
+    Err(anyhow::anyhow!(
+        "Shortcuts are not supported on this platform yet"
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn unregister(_id: u64, _accelerator: &str) {}
+
+#[cfg(not(target_os = "linux"))]
+pub fn unregister_all(_id: u64) {}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn grab(id: u64, keycode: c_uint, modifiers: c_uint) -> Grab {
+        Grab {
+            id,
+            accelerator: format!("{}+{}", modifiers, keycode),
+            keycode,
+            modifiers,
+        }
+    }
+
+    #[test]
+    fn releases_combinations_nobody_else_grabbed() {
+        let kept = [grab(1, 10, xlib::ControlMask)];
+        let removed = [grab(2, 11, xlib::ControlMask)];
+        assert_eq!(released(&kept, &removed), [(11, xlib::ControlMask)]);
+    }
+
+    #[test]
+    fn keeps_combinations_shared_with_another_webview() {
+        let kept = [grab(1, 10, xlib::ControlMask)];
+        let removed = [grab(2, 10, xlib::ControlMask)];
+        assert!(released(&kept, &removed).is_empty());
+
+        // other modifiers are another combination
+        let removed = [grab(2, 10, xlib::ControlMask | xlib::ShiftMask)];
+        assert_eq!(
+            released(&kept, &removed),
+            [(10, xlib::ControlMask | xlib::ShiftMask)]
+        );
+    }
+
+    #[test]
+    fn releases_each_combination_once() {
+        let removed = [grab(1, 10, xlib::Mod1Mask), grab(2, 10, xlib::Mod1Mask)];
+        assert_eq!(released(&[], &removed), [(10, xlib::Mod1Mask)]);
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::{anyhow, bail, Context};
+use ring::{
+    digest::{Context as DigestContext, SHA256},
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519},
+};
+use serde::{Deserialize, Serialize};
+use std::{convert::TryInto, fs, io::Read, path::Path};
+
+use crate::helpers::{from_hex, to_hex};
+
+/// Public key pinned when building the runtime, when set only bundles signed
+/// by this key are allowed to run.
+const TRUSTED_PUBLIC_KEY: Option<&str> = option_env!("WRY_TRUSTED_PUBLIC_KEY");
+
+/// Public key pinned by `wry compile --sign` in the copy of the runtime it
+/// writes, found in the executable by its 16 bytes marker. All zeros until
+/// pinned.
+#[used]
+static PINNED_KEY: [u8; 48] =
+    *b"wry:pinned-key::\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
+/// Ed25519 signature of the bundle and its metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleSignature {
+    /// hex encoded
+    pub public_key: String,
+    /// hex encoded signature of the payload digest
+    pub signature: String,
+}
+
+pub struct SigningKey(Ed25519KeyPair);
+
+impl SigningKey {
+    /// Read a PKCS#8 key generated by `wry keygen`.
+    pub fn open(path: &Path) -> crate::Result<Self> {
+        let pkcs8 = fs::read(path)
+            .with_context(|| format!("Unable to read signing key {}", path.display()))?;
+        Ed25519KeyPair::from_pkcs8(&pkcs8)
+            .map(SigningKey)
+            .map_err(|err| anyhow!("Invalid signing key {}: {}", path.display(), err))
+    }
+
+    pub fn public_key(&self) -> String {
+        to_hex(self.0.public_key().as_ref())
+    }
+
+    /// Pin our public key in a copy of the runtime, it then refuses bundles
+    /// that are unsigned or signed with another key.
+    pub fn pin(&self, runtime: &mut [u8]) -> crate::Result<()> {
+        let pinned = read_pinned_key();
+        let marker = &pinned[..16];
+        let mut positions = runtime
+            .windows(marker.len())
+            .enumerate()
+            .filter(|(_, window)| *window == marker)
+            .map(|(position, _)| position + marker.len());
+        let position = match (positions.next(), positions.next()) {
+            (Some(position), None) => position,
+            _ => bail!("Unable to find where to pin the public key in the runtime"),
+        };
+
+        let public_key = self.0.public_key().as_ref();
+        let slot = &mut runtime[position..position + public_key.len()];
+        if slot.iter().any(|byte| *byte != 0) && slot != public_key {
+            bail!("The runtime is pinned to another public key");
+        }
+        slot.copy_from_slice(public_key);
+        Ok(())
+    }
+
+    pub fn sign(&self, digest: &[u8]) -> BundleSignature {
+        BundleSignature {
+            public_key: self.public_key(),
+            signature: to_hex(self.0.sign(digest).as_ref()),
+        }
+    }
+}
+
+/// Generate a new signing key, the public key is returned hex encoded.
+pub fn generate_key(path: &Path) -> crate::Result<String> {
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|_| anyhow!("Unable to generate a signing key"))?;
+    fs::write(path, pkcs8.as_ref())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(SigningKey::open(path)?.public_key())
+}
+
+/// SHA-256 of the bundle followed by the unsigned metadata.
+pub fn payload_digest(mut bundle: impl Read, metadata: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut context = DigestContext::new(&SHA256);
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = bundle.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+    context.update(metadata);
+    Ok(context.finish().as_ref().to_vec())
+}
+
+fn read_pinned_key() -> [u8; 48] {
+    // the bytes are patched after the build, don't let the compiler fold them
+    unsafe { std::ptr::read_volatile(&PINNED_KEY) }
+}
+
+/// Key bundles must be signed with, hex encoded. Pinned by `wry compile
+/// --sign` or when building the runtime with `WRY_TRUSTED_PUBLIC_KEY`.
+pub fn trusted_key() -> Option<String> {
+    let pinned: [u8; 32] = read_pinned_key()[16..].try_into().ok()?;
+    if pinned.iter().any(|byte| *byte != 0) {
+        return Some(to_hex(&pinned));
+    }
+    TRUSTED_PUBLIC_KEY.map(|key| key.trim().to_lowercase())
+}
+
+/// Check the payload against its signature.
+///
+/// Once a key is pinned in the runtime, unsigned bundles and bundles signed
+/// by another key are refused. Without one the signature only proves the
+/// payload wasn't modified without signing it again.
+pub fn verify(signature: Option<&BundleSignature>, digest: &[u8]) -> crate::Result<()> {
+    verify_with(trusted_key().as_deref(), signature, digest)
+}
+
+fn verify_with(
+    trusted: Option<&str>,
+    signature: Option<&BundleSignature>,
+    digest: &[u8],
+) -> crate::Result<()> {
+    let signature = match (signature, trusted) {
+        (None, None) => return Ok(()),
+        (None, Some(_)) => bail!("Bundle is not signed"),
+        (Some(signature), Some(trusted))
+            if !trusted.eq_ignore_ascii_case(&signature.public_key) =>
+        {
+            bail!("Bundle is signed with an untrusted key")
+        }
+        (Some(signature), _) => signature,
+    };
+
+    let public_key =
+        from_hex(&signature.public_key).ok_or_else(|| anyhow!("Invalid bundle public key"))?;
+    let bytes =
+        from_hex(&signature.signature).ok_or_else(|| anyhow!("Invalid bundle signature"))?;
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(digest, &bytes)
+        .map_err(|_| anyhow!("Bundle signature mismatch"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARKER: &[u8] = b"wry:pinned-key::";
+
+    fn key() -> SigningKey {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        SigningKey(Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap())
+    }
+
+    fn digest(bundle: &[u8], metadata: &[u8]) -> Vec<u8> {
+        payload_digest(bundle, metadata).unwrap()
+    }
+
+    /// Runtime bytes with an unpinned key slot in the middle.
+    fn runtime() -> Vec<u8> {
+        let mut runtime = b"ELF...code".to_vec();
+        runtime.extend_from_slice(MARKER);
+        runtime.extend_from_slice(&[0; 32]);
+        runtime.extend_from_slice(b"...more code");
+        runtime
+    }
+
+    #[test]
+    fn signed_bundles_verify() {
+        let key = key();
+        let signature = key.sign(&digest(b"bundle", b"{}"));
+        verify_with(None, Some(&signature), &digest(b"bundle", b"{}")).unwrap();
+        let trusted = key.public_key();
+        verify_with(
+            Some(trusted.as_str()),
+            Some(&signature),
+            &digest(b"bundle", b"{}"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn tampered_bundles_are_refused() {
+        let key = key();
+        let signature = key.sign(&digest(b"bundle", b"{}"));
+        assert!(verify_with(None, Some(&signature), &digest(b"bundlf", b"{}")).is_err());
+    }
+
+    #[test]
+    fn tampered_metadata_is_refused() {
+        let key = key();
+        let signature = key.sign(&digest(b"bundle", br#"{"overlay":null}"#));
+        let tampered = digest(b"bundle", br#"{"overlay":"patches"}"#);
+        assert!(verify_with(None, Some(&signature), &tampered).is_err());
+    }
+
+    #[test]
+    fn other_keys_are_refused() {
+        let trusted = key().public_key();
+        let digest = digest(b"bundle", b"{}");
+        let signature = key().sign(&digest);
+        assert!(verify_with(Some(trusted.as_str()), Some(&signature), &digest).is_err());
+
+        // the signature must match the key it claims
+        let mut forged = key().sign(&digest);
+        forged.public_key = trusted.clone();
+        assert!(verify_with(Some(trusted.as_str()), Some(&forged), &digest).is_err());
+    }
+
+    #[test]
+    fn unsigned_bundles_need_an_unpinned_runtime() {
+        let digest = digest(b"bundle", b"{}");
+        verify_with(None, None, &digest).unwrap();
+        let trusted = key().public_key();
+        assert!(verify_with(Some(trusted.as_str()), None, &digest).is_err());
+    }
+
+    #[test]
+    fn pins_the_public_key_after_the_marker() {
+        let key = key();
+        let mut runtime = runtime();
+        key.pin(&mut runtime).unwrap();
+
+        let position = runtime
+            .windows(MARKER.len())
+            .position(|window| window == MARKER)
+            .unwrap()
+            + MARKER.len();
+        assert_eq!(to_hex(&runtime[position..position + 32]), key.public_key());
+        assert!(runtime.ends_with(b"...more code"));
+
+        // pinning the same key again is fine
+        key.pin(&mut runtime).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_pin_another_key() {
+        let mut runtime = runtime();
+        key().pin(&mut runtime).unwrap();
+        let pinned = runtime.clone();
+        assert!(key().pin(&mut runtime).is_err());
+        assert_eq!(runtime, pinned);
+    }
+
+    #[test]
+    fn needs_exactly_one_marker() {
+        let mut missing = b"no marker here".to_vec();
+        assert!(key().pin(&mut missing).is_err());
+
+        let mut twice = runtime();
+        twice.extend(runtime());
+        assert!(key().pin(&mut twice).is_err());
+    }
+}
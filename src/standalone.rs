@@ -24,40 +24,209 @@ use std::cell::RefCell;
 use std::pin::Pin;
 use std::rc::Rc;
 
-use crate::embed_assets::{Assets, EmbeddedAssets};
+use crate::assets::{Assets, DirAssets, OverlayAssets};
+use crate::embed_assets::EmbeddedAssets;
+use crate::instance::AppOptions;
+use crate::signing::{self, BundleSignature, SigningKey};
 pub struct EmbeddedModuleLoader(pub String);
 
-#[derive(Deserialize, Serialize)]
-pub struct Metadata {}
+#[derive(Default, Deserialize, Serialize)]
+pub struct Metadata {
+    /// covers the bundle and the rest of the metadata
+    #[serde(default)]
+    pub signature: Option<BundleSignature>,
+    /// directory layered over the embedded assets, relative to the executable
+    #[serde(default)]
+    pub overlay: Option<String>,
+    #[serde(default)]
+    pub app_id: Option<String>,
+    #[serde(default)]
+    pub single_instance: bool,
+    /// URL schemes opening the app, like `myapp` for `myapp://open?id=42`
+    #[serde(default)]
+    pub schemes: Vec<String>,
+}
 
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
 pub fn compile_command(
     assets: &EmbeddedAssets,
+    metadata: Metadata,
     output: Option<std::path::PathBuf>,
     target: Option<String>,
+    signing_key: Option<&SigningKey>,
 ) -> crate::Result<()> {
-    let original_binary = get_base_binary()?;
+    let mut original_binary = get_base_binary()?;
+    match (signing_key, signing::trusted_key()) {
+        (Some(signing_key), Some(trusted)) if signing_key.public_key() != trusted => {
+            anyhow::bail!("This runtime only runs bundles signed with {}", trusted)
+        }
+        (Some(signing_key), _) => signing_key.pin(&mut original_binary)?,
+        (None, Some(trusted)) => anyhow::bail!(
+            "This runtime only runs bundles signed with {}, pass --sign",
+            trusted
+        ),
+        (None, None) => {}
+    }
+
+    if let Some(scheme) = metadata
+        .schemes
+        .iter()
+        .find(|scheme| !is_valid_scheme(scheme))
+    {
+        anyhow::bail!("Invalid URL scheme {}", scheme);
+    }
+    let is_linux = target
+        .as_deref()
+        .map_or(cfg!(target_os = "linux"), |target| target.contains("linux"));
+    let registers_schemes = is_linux && !metadata.schemes.is_empty();
 
-    let final_bin = create_standalone_binary(original_binary, assets)?;
+    let final_bin = create_standalone_binary(original_binary, assets, metadata, signing_key)?;
 
     let output = output
         .or_else(|| Some(std::path::PathBuf::from("compiled-bin-test")))
         .unwrap();
 
-    write_standalone_binary(output, target, final_bin)?;
+    let output = write_standalone_binary(output, target, final_bin)?;
+
+    if registers_schemes {
+        println!(
+            "The URL schemes are registered for the user when {} runs",
+            output.display()
+        );
+    }
 
     Ok(())
 }
 
+/// `scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )`
+fn is_valid_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    chars.next().map_or(false, |c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+}
+
+/// Register the running binary as handler of the URL schemes with a
+/// desktop entry of the user, rewritten when the binary moved.
+#[cfg(target_os = "linux")]
+fn register_schemes(app_id: Option<&str>, schemes: &[String]) -> crate::Result<()> {
+    // an AppImage is mounted at a new path on every launch
+    let binary = match std::env::var_os("APPIMAGE") {
+        Some(appimage) => std::path::PathBuf::from(appimage),
+        None => current_exe()?,
+    };
+    let name = binary
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // never named after the app id, a user entry would shadow the launcher
+    // installed by a package
+    let file_name = match app_id {
+        Some(app_id) => format!("{}-url-handler.desktop", app_id),
+        None => format!("wry-{}-url-handler.desktop", name),
+    };
+    let mime_types: String = schemes
+        .iter()
+        .map(|scheme| format!("x-scheme-handler/{};", scheme.to_lowercase()))
+        .collect();
+    let entry = format!(
+        "[Desktop Entry]\nType=Application\nName={}\nExec={} %u\nTerminal=false\nNoDisplay=true\nMimeType={}\n",
+        escape_string(&name),
+        exec_arg(&binary.to_string_lossy()),
+        mime_types
+    );
+
+    let dir = applications_dir().context("Unable to find the applications directory")?;
+    let path = dir.join(&file_name);
+    let current = std::fs::read_to_string(&path).ok();
+    if current.as_deref() == Some(entry.as_str()) {
+        return Ok(());
+    }
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(&path, entry)?;
+    // the association is by file name, a moved binary only updates Exec
+    if current.is_some() {
+        return Ok(());
+    }
+
+    for scheme in schemes {
+        let mime_type = format!("x-scheme-handler/{}", scheme.to_lowercase());
+        let status = std::process::Command::new("xdg-mime")
+            .args(&["default", &file_name, &mime_type])
+            .status()
+            .context("Unable to run xdg-mime")?;
+        if !status.success() {
+            anyhow::bail!("xdg-mime failed to register {}", mime_type);
+        }
+    }
+    // only refreshes the cache, desktops without it read the entries
+    let _ = std::process::Command::new("update-desktop-database")
+        .arg(&dir)
+        .status();
+    Ok(())
+}
+
+/// `$XDG_DATA_HOME/applications`, `~/.local/share/applications` by default.
+#[cfg(target_os = "linux")]
+fn applications_dir() -> Option<std::path::PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(std::path::PathBuf::from)
+        // relative paths are invalid and must be ignored
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::PathBuf::from(home).join(".local/share"))
+        })?;
+    Some(data_home.join("applications"))
+}
+
+/// Escape a string value of a desktop entry.
+#[cfg(target_os = "linux")]
+fn escape_string(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            '\t' => "\\t".to_string(),
+            '\r' => "\\r".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Quote an argument of the `Exec` key, the quoting escapes come before the
+/// ones of string values so a backslash ends up as four.
+#[cfg(target_os = "linux")]
+fn exec_arg(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            // field codes like %u
+            '%' => quoted.push_str("%%"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    escape_string(&quoted)
+}
+
 fn create_standalone_binary(
     mut original_bin: Vec<u8>,
     assets: &EmbeddedAssets,
+    mut metadata: Metadata,
+    signing_key: Option<&SigningKey>,
 ) -> crate::Result<Vec<u8>> {
-    let mut source_code = serde_json::to_string(&assets)?.as_bytes().to_vec();
+    let mut source_code = assets.to_bundle()?;
 
-    let metadata = Metadata {};
+    if let Some(signing_key) = signing_key {
+        let digest = signing::payload_digest(&source_code[..], &serde_json::to_vec(&metadata)?)?;
+        metadata.signature = Some(signing_key.sign(&digest));
+    }
     let mut metadata = serde_json::to_string(&metadata)?.as_bytes().to_vec();
 
     let bundle_pos = original_bin.len();
@@ -80,7 +249,7 @@ fn write_standalone_binary(
     output: std::path::PathBuf,
     target: Option<String>,
     final_bin: Vec<u8>,
-) -> crate::Result<()> {
+) -> crate::Result<std::path::PathBuf> {
     let output = match target {
         Some(target) => {
             if target.contains("windows") {
@@ -124,16 +293,16 @@ fn write_standalone_binary(
     #[cfg(unix)]
     {
         let perms = std::fs::Permissions::from_mode(0o777);
-        std::fs::set_permissions(output, perms)?;
+        std::fs::set_permissions(&output, perms)?;
     }
 
-    Ok(())
+    Ok(output)
 }
 
 pub fn extract_standalone() -> crate::Result<Option<(Metadata, EmbeddedAssets)>> {
     let current_exe_path = current_exe()?;
 
-    let mut current_exe = File::open(current_exe_path)?;
+    let mut current_exe = File::open(&current_exe_path)?;
     let trailer_pos = current_exe.seek(SeekFrom::End(-24))?;
     let mut trailer = [0; 24];
     current_exe.read_exact(&mut trailer)?;
@@ -146,18 +315,31 @@ pub fn extract_standalone() -> crate::Result<Option<(Metadata, EmbeddedAssets)>>
     let metadata_pos = rest;
     let bundle_pos = u64_from_bytes(bundle_pos)?;
     let metadata_pos = u64_from_bytes(metadata_pos)?;
-    let bundle_len = metadata_pos - bundle_pos;
+    if bundle_pos > metadata_pos || metadata_pos > trailer_pos {
+        anyhow::bail!("Invalid trailer in the current executable");
+    }
     let metadata_len = trailer_pos - metadata_pos;
-    current_exe.seek(SeekFrom::Start(bundle_pos))?;
-
-    let bundle = read_string_slice(&mut current_exe, bundle_pos, bundle_len)
-        .context("Failed to read source bundle from the current executable")?;
 
     let metadata = read_string_slice(&mut current_exe, metadata_pos, metadata_len)
         .context("Failed to read metadata from the current executable")?;
 
-    let metadata: Metadata = serde_json::from_str(&metadata).unwrap();
-    let assets: EmbeddedAssets = serde_json::from_str(&bundle).unwrap();
+    let mut metadata: Metadata = serde_json::from_str(&metadata)?;
+
+    // the signature was computed before being added to the metadata
+    let signature = metadata.signature.take();
+    current_exe.seek(SeekFrom::Start(bundle_pos))?;
+    let digest = signing::payload_digest(
+        (&mut current_exe).take(metadata_pos - bundle_pos),
+        &serde_json::to_vec(&metadata)?,
+    )?;
+    signing::verify(signature.as_ref(), &digest)
+        .context("Refusing to start, the embedded bundle failed verification")?;
+    metadata.signature = signature;
+
+    // assets content is read from the executable on demand
+    let assets =
+        EmbeddedAssets::from_bundle(&current_exe_path, bundle_pos, metadata_pos - bundle_pos)
+            .context("Failed to read source bundle from the current executable")?;
 
     Ok(Some((metadata, assets)))
 }
@@ -165,7 +347,33 @@ pub fn extract_standalone() -> crate::Result<Option<(Metadata, EmbeddedAssets)>>
 pub async fn run(assets: EmbeddedAssets, metadata: Metadata) -> crate::Result<()> {
     //println!("SOURCE: {}", source_code);
     let entry_point_file = "index.js";
-    crate::run_wry(entry_point_file, Some(assets)).await
+
+    let overlay = metadata.overlay.as_ref().and_then(|overlay| {
+        let exe_dir = current_exe().ok()?.parent()?.to_owned();
+        Some(exe_dir.join(overlay))
+    });
+    let assets: Rc<dyn Assets> = match overlay {
+        // the overlay is optional, patches can be dropped in later
+        Some(overlay) if overlay.is_dir() => Rc::new(
+            OverlayAssets::new(Box::new(DirAssets::new(&overlay)?), Box::new(assets))
+                .with_protected(entry_point_file),
+        ),
+        _ => Rc::new(assets),
+    };
+
+    #[cfg(target_os = "linux")]
+    if !metadata.schemes.is_empty() {
+        if let Err(err) = register_schemes(metadata.app_id.as_deref(), &metadata.schemes) {
+            eprintln!("Unable to register the URL schemes: {:#}", err);
+        }
+    }
+
+    let app = AppOptions {
+        app_id: metadata.app_id,
+        single_instance: metadata.single_instance,
+        schemes: metadata.schemes,
+    };
+    crate::run_wry(entry_point_file, assets, &app).await
 }
 
 fn get_base_binary() -> crate::Result<Vec<u8>> {
@@ -231,3 +439,45 @@ impl ModuleLoader for EmbeddedModuleLoader {
         .boxed_local()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_schemes() {
+        for scheme in &["myapp", "my-app", "com.example.app", "web+app", "a1"] {
+            assert!(is_valid_scheme(scheme), "{}", scheme);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_schemes() {
+        for scheme in &[
+            "", "1app", "-app", "my app", "my_app", "app:", "app/x", "été",
+        ] {
+            assert!(!is_valid_scheme(scheme), "{}", scheme);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn quotes_exec_arguments() {
+        assert_eq!(exec_arg("/opt/my app/app"), "\"/opt/my app/app\"");
+        assert_eq!(exec_arg("/opt/50%/app"), "\"/opt/50%%/app\"");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn escapes_exec_reserved_characters() {
+        assert_eq!(exec_arg("/a\\b"), r#""/a\\\\b""#);
+        assert_eq!(exec_arg("/$HOME/`x`"), r#""/\\$HOME/\\`x\\`""#);
+        assert_eq!(exec_arg("/say \"hi\""), r#""/say \\"hi\\"""#);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn escapes_string_values() {
+        assert_eq!(escape_string("a\\b\nc"), r"a\\b\nc");
+    }
+}
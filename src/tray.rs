@@ -0,0 +1,94 @@
+use serde::Deserialize;
+
+use crate::assets::Assets;
+use crate::menu::MenuItem;
+
+#[cfg(target_os = "linux")]
+use crate::event::Event;
+#[cfg(target_os = "linux")]
+use anyhow::Context;
+#[cfg(target_os = "linux")]
+use glib::{translate::from_glib, ObjectExt};
+#[cfg(target_os = "linux")]
+use gtk::prelude::*;
+#[cfg(target_os = "linux")]
+use std::{cell::RefCell, collections::HashMap};
+
+#[cfg(target_os = "linux")]
+thread_local! {
+  static TRAYS: RefCell<HashMap<u64, glib::Object>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrayOptions {
+    /// path of the icon in the app assets
+    pub icon: String,
+    #[serde(default)]
+    pub tooltip: Option<String>,
+    /// shown on right click
+    #[serde(default)]
+    pub menu: Vec<MenuItem>,
+}
+
+/// Show a tray icon owned by the webview, clicks are reported with
+/// `trayClicked` and `menuItemClicked` events.
+#[cfg(target_os = "linux")]
+pub fn set_tray(id: u64, options: &TrayOptions, assets: &dyn Assets) -> crate::Result<()> {
+    let bytes = assets
+        .get(&options.icon)
+        .ok_or_else(|| anyhow::anyhow!("Tray icon {} not found", options.icon))?;
+    let pixbuf = crate::helpers::pixbuf_from_bytes(&bytes)
+        .with_context(|| format!("Invalid tray icon {}", options.icon))?;
+
+    remove_tray(id);
+
+    // GtkStatusIcon is deprecated and left out of the gtk bindings, it still
+    // is the only tray available without extra system libraries
+    let status_icon_type = unsafe { from_glib(gtk_sys::gtk_status_icon_get_type()) };
+    let icon = glib::Object::new(
+        status_icon_type,
+        &[
+            ("pixbuf", &pixbuf),
+            ("tooltip-text", &options.tooltip),
+            ("visible", &true),
+        ],
+    )?;
+
+    icon.connect_local("activate", false, move |_| {
+        crate::push_event(id, Event::TrayClicked);
+        None
+    })?;
+
+    if !options.menu.is_empty() {
+        let menu = crate::menu::build_menu(id, &options.menu)?;
+        // (status icon, button, activate time)
+        icon.connect_local("popup-menu", false, move |values| {
+            let button = values.get(1).and_then(|value| value.get_some::<u32>().ok());
+            let time = values.get(2).and_then(|value| value.get_some::<u32>().ok());
+            menu.popup_easy(button.unwrap_or(3), time.unwrap_or_default());
+            None
+        })?;
+    }
+
+    TRAYS.with(|cell| cell.borrow_mut().insert(id, icon));
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn remove_tray(id: u64) {
+    if let Some(icon) = TRAYS.with(|cell| cell.borrow_mut().remove(&id)) {
+        // hide it right away, gtk may keep a reference for a while
+        let _ = icon.set_property("visible", &false);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_tray(_id: u64, _options: &TrayOptions, _assets: &dyn Assets) -> crate::Result<()> {
+    Err(anyhow::anyhow!(
+        "Tray icons are not supported on this platform yet"
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn remove_tray(_id: u64) {}